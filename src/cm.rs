@@ -1,23 +1,44 @@
 pub mod action;
+pub mod ansi;
+pub mod args;
+pub mod batch;
 mod bottom_edit_field;
+pub mod catalog;
+mod child_stream;
 pub mod config;
+mod config_file;
 pub mod ctrlc;
 mod cursor;
 mod edit_field;
+pub mod export;
 mod geometry;
 mod global;
 mod item_list;
 mod key_map;
 mod key_map_settings;
 mod key_stroke;
+mod matcher;
 pub mod migration;
 mod output_buffer;
+mod output_line;
+mod output_list;
+mod preview;
 mod profile;
+pub mod pty;
+mod quickfix;
+mod rule;
+pub mod session;
+mod severity;
+pub mod shell;
 mod string_list;
 mod style;
+mod tab;
+pub mod template;
 pub mod unicode;
 
 pub use self::bottom_edit_field::*;
+pub use self::child_stream::*;
+pub use self::config_file::*;
 pub use self::cursor::*;
 pub use self::edit_field::*;
 pub use self::geometry::*;
@@ -26,7 +47,15 @@ pub use self::item_list::*;
 pub use self::key_map::*;
 pub use self::key_map_settings::*;
 pub use self::key_stroke::*;
+pub use self::matcher::*;
 pub use self::output_buffer::*;
+pub use self::output_line::*;
+pub use self::output_list::*;
+pub use self::preview::*;
 pub use self::profile::*;
+pub use self::quickfix::*;
+pub use self::rule::*;
+pub use self::severity::*;
 pub use self::string_list::*;
 pub use self::style::*;
+pub use self::tab::*;
@@ -33,7 +33,23 @@ pub const PAGE_UP: Type = 28;
 pub const PAGE_DOWN: Type = 29;
 pub const REGEXS_MODE: Type = 30;
 pub const CMDS_MODE: Type = 31;
-pub const LEN: usize = 32;
+pub const TOGGLE_PTY: Type = 32;
+pub const CYCLE_STREAM_FILTER: Type = 33;
+pub const NEW_TAB: Type = 34;
+pub const NEXT_TAB: Type = 35;
+pub const PREV_TAB: Type = 36;
+pub const CLOSE_TAB: Type = 37;
+pub const RENAME_TAB: Type = 38;
+pub const FORWARD: Type = 39;
+pub const HISTORY_MODE: Type = 40;
+pub const RULES_MODE: Type = 41;
+pub const CYCLE_REGEX_SELECTION: Type = 42;
+pub const NEXT_ERROR: Type = 43;
+pub const PREV_ERROR: Type = 44;
+pub const QUICKFIX_MODE: Type = 45;
+pub const EXPORT_MATCHES: Type = 46;
+pub const TOGGLE_PREVIEW: Type = 47;
+pub const LEN: usize = 48;
 
 pub const NAMES: [&str; LEN] = [
     "up",
@@ -68,6 +84,22 @@ pub const NAMES: [&str; LEN] = [
     "page_down",
     "regexs_mode",
     "cmds_mode",
+    "toggle_pty",
+    "cycle_stream_filter",
+    "new_tab",
+    "next_tab",
+    "prev_tab",
+    "close_tab",
+    "rename_tab",
+    "forward",
+    "history_mode",
+    "rules_mode",
+    "cycle_regex_selection",
+    "next_error",
+    "prev_error",
+    "quickfix_mode",
+    "export_matches",
+    "toggle_preview",
 ];
 
 pub fn from_str(s: &str) -> Result<Type, String> {
@@ -0,0 +1,329 @@
+use std::iter::Peekable;
+use std::slice::Iter;
+
+pub const ESC: u8 = 0x1b;
+
+/// Text attributes set by the SGR (Select Graphic Rendition) escape
+/// sequences: `ESC [ <params> m`. Colors are the indices of the 8
+/// basic ANSI colors which match the ncurses COLOR_* constants.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Style {
+    pub fg: Option<i16>,
+    pub bg: Option<i16>,
+    pub bold: bool,
+    pub underline: bool,
+    pub reverse: bool,
+}
+
+impl Style {
+    pub fn is_default(&self) -> bool {
+        *self == Style::default()
+    }
+
+    fn apply_sgr(&mut self, params: &str) {
+        let mut params = params
+            .split(&[';', ':'][..])
+            .map(|x| x.parse::<u32>().unwrap_or(0));
+
+        // NOTE: `ESC [ m` is the same as `ESC [ 0 m`
+        if params.clone().next().is_none() {
+            *self = Style::default();
+        }
+
+        while let Some(param) = params.next() {
+            match param {
+                0 => *self = Style::default(),
+                1 => self.bold = true,
+                4 => self.underline = true,
+                7 => self.reverse = true,
+                22 => self.bold = false,
+                24 => self.underline = false,
+                27 => self.reverse = false,
+                30..=37 => self.fg = Some((param - 30) as i16),
+                38 => self.fg = extended_color(&mut params),
+                39 => self.fg = None,
+                40..=47 => self.bg = Some((param - 40) as i16),
+                48 => self.bg = extended_color(&mut params),
+                49 => self.bg = None,
+                // NOTE: we only have 8 colors, so the bright ones are
+                // approximated with bold
+                90..=97 => {
+                    self.fg = Some((param - 90) as i16);
+                    self.bold = true;
+                }
+                100..=107 => self.bg = Some((param - 100) as i16),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Parses the rest of `38;5;<n>` and `38;2;<r>;<g>;<b>` and maps the
+/// color onto the closest one of the 8 basic colors.
+fn extended_color<I: Iterator<Item = u32>>(params: &mut I) -> Option<i16> {
+    let bits = |r: u32, g: u32, b: u32, threshold: u32| {
+        (r > threshold) as i16 | ((g > threshold) as i16) << 1 | ((b > threshold) as i16) << 2
+    };
+
+    match params.next()? {
+        5 => match params.next()? {
+            n @ 0..=7 => Some(n as i16),
+            n @ 8..=15 => Some((n - 8) as i16),
+            n @ 16..=231 => {
+                let n = n - 16;
+                Some(bits(n / 36, n / 6 % 6, n % 6, 2))
+            }
+            n => Some(if n >= 244 { 7 } else { 0 }),
+        },
+        2 => {
+            let r = params.next()?;
+            let g = params.next()?;
+            let b = params.next()?;
+            Some(bits(r, g, b, 127))
+        }
+        _ => None,
+    }
+}
+
+/// A range of bytes within the escape-stripped line that is rendered
+/// with a particular [Style](struct.Style.html)
+#[derive(Debug, PartialEq, Clone)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub style: Style,
+}
+
+/// Turns the raw output of a child process into plain text and a list of
+/// styled [Span](struct.Span.html)s. The current style is carried over
+/// from one line to the next one the same way a terminal does it.
+#[derive(Clone)]
+pub struct Parser {
+    pub style: Style,
+    pub tabsize: usize,
+}
+
+impl Parser {
+    pub fn new(tabsize: usize) -> Self {
+        Self {
+            style: Style::default(),
+            tabsize,
+        }
+    }
+
+    /// Strips all of the escape sequences from the `input`. SGR sequences
+    /// are turned into [Span](struct.Span.html)s, everything else is
+    /// dropped. Tabs are expanded into spaces:
+    ///
+    /// ```text
+    /// |--------|    |--------|--------| 8 spaces/tab (tabsize = 8)
+    /// |\t      | => |........|        | 8 spaces
+    /// |\ta     | => |........|a       | 8 spaces + "a"
+    /// |aaa\t   | => |aaa.....|        | "aaa" + 5 spaces
+    /// ```
+    ///
+    /// Carriage return (`\r`) and the erase line sequence (`ESC [ 2 K`)
+    /// clear everything that was written into the line so far, which is
+    /// how the progress bars are usually redrawn. A `\r` which is the last
+    /// character of the line or is followed by `\n` is simply dropped.
+    ///
+    /// The `input` is not required to be a valid UTF-8.
+    pub fn parse_line(&mut self, input: &[u8]) -> (Vec<u8>, Vec<Span>) {
+        let mut text = Vec::with_capacity(input.len());
+        let mut spans = Vec::new();
+        let mut span_start = 0;
+        let mut char_count = 0;
+        let mut bytes = input.iter().peekable();
+
+        while let Some(&b) = bytes.next() {
+            match b {
+                ESC => match skip_escape_sequence(&mut bytes) {
+                    Some((params, b'm')) => {
+                        let mut style = self.style;
+                        style.apply_sgr(&params);
+                        if style != self.style {
+                            self.close_span(&text, span_start, &mut spans);
+                            span_start = text.len();
+                            self.style = style;
+                        }
+                    }
+                    Some((params, b'K')) if params == "1" || params == "2" => {
+                        text.clear();
+                        spans.clear();
+                        span_start = 0;
+                        char_count = 0;
+                    }
+                    _ => {}
+                },
+                b'\r' => match bytes.peek() {
+                    None | Some(b'\n') => {}
+                    Some(_) => {
+                        text.clear();
+                        spans.clear();
+                        span_start = 0;
+                        char_count = 0;
+                    }
+                },
+                b'\t' => {
+                    if self.tabsize > 0 {
+                        let space_count = self.tabsize - (char_count % self.tabsize);
+                        char_count += space_count;
+                        text.extend(std::iter::repeat_n(b' ', space_count));
+                    }
+                }
+                b => {
+                    // NOTE: UTF-8 continuation bytes do not start a new character
+                    if b & 0xC0 != 0x80 {
+                        char_count += 1;
+                    }
+                    text.push(b);
+                }
+            }
+        }
+
+        self.close_span(&text, span_start, &mut spans);
+
+        (text, spans)
+    }
+
+    fn close_span(&self, text: &[u8], start: usize, spans: &mut Vec<Span>) {
+        if !self.style.is_default() && start < text.len() {
+            spans.push(Span {
+                start,
+                end: text.len(),
+                style: self.style,
+            })
+        }
+    }
+}
+
+/// Skips the escape sequence right after ESC. Returns the parameters and
+/// the final byte of the sequence if it is CSI.
+fn skip_escape_sequence(bytes: &mut Peekable<Iter<u8>>) -> Option<(String, u8)> {
+    match bytes.next()? {
+        // CSI: ESC [ <parameter bytes> <intermediate bytes> <final byte>
+        b'[' => {
+            let mut params = String::new();
+            while let Some(&&b) = bytes.peek() {
+                if (0x30..=0x3f).contains(&b) {
+                    params.push(b as char);
+                    bytes.next();
+                } else {
+                    break;
+                }
+            }
+            while let Some(&&b) = bytes.peek() {
+                if (0x20..=0x2f).contains(&b) {
+                    bytes.next();
+                } else {
+                    break;
+                }
+            }
+            bytes.next().map(|&b| (params, b))
+        }
+        // OSC: ESC ] ... (BEL | ESC \)
+        b']' => {
+            while let Some(&b) = bytes.next() {
+                match b {
+                    0x07 => break,
+                    ESC => {
+                        if bytes.peek() == Some(&&b'\\') {
+                            bytes.next();
+                        }
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        let red = Style {
+            fg: Some(1),
+            ..Style::default()
+        };
+
+        let mut parser = Parser::new(8);
+        assert_eq!(
+            parser.parse_line(b"\x1b[31merror\x1b[0m: oops"),
+            (
+                b"error: oops".to_vec(),
+                vec![Span {
+                    start: 0,
+                    end: 5,
+                    style: red
+                }]
+            )
+        );
+        assert_eq!(
+            parser.parse_line(b"\x1b[1m\x1b[Kfoo\x1b]0;title\x07\tbar"),
+            (
+                b"foo     bar".to_vec(),
+                vec![Span {
+                    start: 0,
+                    end: 11,
+                    style: Style {
+                        bold: true,
+                        ..Style::default()
+                    }
+                }]
+            )
+        );
+        assert!(parser.style.bold);
+        assert_eq!(
+            parser.parse_line(b"\x1b[mplain"),
+            (b"plain".to_vec(), vec![])
+        );
+    }
+
+    #[test]
+    fn test_parse_carriage_return() {
+        let green = Style {
+            fg: Some(2),
+            ..Style::default()
+        };
+
+        let mut parser = Parser::new(8);
+        assert_eq!(
+            parser.parse_line(b"10%\r\x1b[32m20%\r\x1b[2K30%\r"),
+            (
+                b"30%".to_vec(),
+                vec![Span {
+                    start: 0,
+                    end: 3,
+                    style: green
+                }]
+            )
+        );
+        assert_eq!(
+            parser.parse_line(b"\x1b[0mcrlf\r\n"),
+            (b"crlf\n".to_vec(), vec![])
+        );
+    }
+
+    #[test]
+    fn test_parse_non_utf8_line() {
+        let mut parser = Parser::new(4);
+        assert_eq!(
+            parser.parse_line(b"\xf0\x9f\x98\x82\t\xe9\t!"),
+            (b"\xf0\x9f\x98\x82   \xe9   !".to_vec(), vec![])
+        );
+    }
+
+    #[test]
+    fn test_extended_colors() {
+        let mut style = Style::default();
+        style.apply_sgr("38;5;9;48;2;0;0;255");
+        assert_eq!(style.fg, Some(1));
+        assert_eq!(style.bg, Some(4));
+    }
+}
@@ -0,0 +1,203 @@
+use super::*;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: cm [OPTIONS] [<cmdline>]
+       cm [OPTIONS] -- <program> [<args>...]
+
+Runs <cmdline> in the shell and lets you navigate its output, match it
+with regexes and run commands on the matches.
+
+Options:
+    -h, --help              Print this help and exit
+    -V, --version           Print the version and exit
+    -c, --config <path>     Use <path> instead of $XDG_CONFIG_HOME/cm.conf
+    -s, --shell <path>      Run the commands with <path> instead of the
+                            shell from the configuration
+    -r, --regex <pattern>   Use <pattern> as the current regex until another
+                            one is picked (it is not saved)
+    -C, --cmd <template>    Use <template> as the current command until
+                            another one is picked (it is not saved)
+    -d, --cwd <dir>         Change the working directory to <dir> first
+    -f, --file <path>       Open the file instead of running a command.
+                            Can be repeated, every file gets its own tab
+        --follow            Keep reading the files as they grow (tail -f)
+        --resume            Restore the tabs of the previous session
+        --batch             Do not start the UI, print the matches of the
+                            current regex instead
+        --json              Same as --batch but print the matches as JSON
+        --export <path>     Write the matched locations to <path> when cm
+                            exits (or when --batch is done), as JSON if it
+                            ends with .json or .sarif, otherwise as
+                            file:line:col: message for `vim -q`
+    --                      Treat the rest of the arguments as the command
+                            and its arguments instead of a <cmdline>
+
+The settings are read from cm.conf in $XDG_CONFIG_DIRS (/etc/xdg), then
+from $XDG_CONFIG_HOME/cm.conf and then from the .cm.conf found in the
+working directory or above it. Every setting is taken from the last file
+that has it and is saved back into that file.
+";
+
+/// The command line arguments of cm. See [USAGE](constant.USAGE.html)
+pub struct Args {
+    pub help: bool,
+    pub version: bool,
+    pub config: Option<PathBuf>,
+    pub shell: Option<PathBuf>,
+    pub regex: Option<String>,
+    pub cmd: Option<String>,
+    pub cwd: Option<PathBuf>,
+    pub resume: bool,
+    pub batch: Option<batch::Format>,
+    /// see [export::save](../export/fn.save.html)
+    pub export: Option<PathBuf>,
+    /// the files to open instead of running a command. Each one of them
+    /// is opened in its own tab.
+    pub files: Vec<PathBuf>,
+    /// keep reading the `files` as they grow (`tail -f`)
+    pub follow: bool,
+    pub cmdline: Option<String>,
+    /// the command and its arguments provided after `--`
+    pub argv: Vec<String>,
+}
+
+impl Args {
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+        let mut result = Self {
+            help: false,
+            version: false,
+            config: None,
+            shell: None,
+            regex: None,
+            cmd: None,
+            cwd: None,
+            resume: false,
+            batch: None,
+            export: None,
+            files: Vec::new(),
+            follow: false,
+            cmdline: None,
+            argv: Vec::new(),
+        };
+
+        while let Some(arg) = args.next() {
+            // NOTE: `--flag=value` is the same as `--flag value`
+            let (flag, inline_value) = match arg.find('=') {
+                Some(pos) if arg.starts_with("--") => {
+                    (arg[..pos].to_string(), Some(arg[pos + 1..].to_string()))
+                }
+                _ => (arg.clone(), None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| format!("`{}` requires a value. See `cm --help`", flag))
+            };
+
+            match flag.as_str() {
+                "-h" | "--help" => result.help = true,
+                "-V" | "--version" => result.version = true,
+                "-c" | "--config" => result.config = Some(PathBuf::from(value()?)),
+                "-s" | "--shell" => result.shell = Some(PathBuf::from(value()?)),
+                "-r" | "--regex" => result.regex = Some(value()?),
+                "-C" | "--cmd" => result.cmd = Some(value()?),
+                "-d" | "--cwd" => result.cwd = Some(PathBuf::from(value()?)),
+                "-f" | "--file" => result.files.push(PathBuf::from(value()?)),
+                "--follow" => result.follow = true,
+                "--resume" => result.resume = true,
+                "--batch" => result.batch = result.batch.or(Some(batch::Format::Text)),
+                "--json" => result.batch = Some(batch::Format::Json),
+                "--export" => result.export = Some(PathBuf::from(value()?)),
+                "--" => {
+                    result.argv = args.by_ref().collect();
+                    if result.argv.is_empty() {
+                        return Err("`--` requires a command to run".to_string());
+                    }
+                }
+                // NOTE: `cm make -j4` is most likely an unquoted command
+                // line rather than an unknown flag
+                flag if flag.starts_with('-') && flag != "-" && result.cmdline.is_none() => {
+                    return Err(format!("Unknown flag `{}`. See `cm --help`", flag));
+                }
+                _ => {
+                    if let Some(cmdline) = &result.cmdline {
+                        return Err(format!(
+                            "Unexpected argument `{}` after `{}`. Quote the whole command line \
+                             (cm '{} {}') or pass it after `--`",
+                            arg, cmdline, cmdline, arg
+                        ));
+                    }
+                    result.cmdline = Some(arg);
+                }
+            }
+        }
+
+        if result.cmdline.is_some() && !result.argv.is_empty() {
+            return Err("Either a <cmdline> or a command after `--` can be provided".to_string());
+        }
+
+        Ok(result)
+    }
+
+    /// The command line to run provided either as `<cmdline>` or after `--`
+    pub fn cmdline(&self) -> Option<String> {
+        if self.argv.is_empty() {
+            self.cmdline.clone()
+        } else {
+            Some(shell::join_argv(&self.argv))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|x| x.to_string()))
+    }
+
+    #[test]
+    fn test_parse_files() {
+        let args = parse(&["-f", "a.log", "--follow", "--file", "b.log"]).unwrap();
+        assert_eq!(
+            args.files,
+            vec![PathBuf::from("a.log"), PathBuf::from("b.log")]
+        );
+        assert!(args.follow);
+        assert!(args.cmdline.is_none());
+
+        assert!(parse(&["make", "-f"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_flags() {
+        let args = parse(&[
+            "--config=/tmp/cm.conf",
+            "-r",
+            r"(\S+):(\d+)",
+            "--cwd",
+            "/tmp",
+            "make -j4",
+        ])
+        .unwrap();
+        assert_eq!(args.config, Some(PathBuf::from("/tmp/cm.conf")));
+        assert_eq!(args.regex.as_deref(), Some(r"(\S+):(\d+)"));
+        assert_eq!(args.cwd, Some(PathBuf::from("/tmp")));
+        assert_eq!(args.cmdline().as_deref(), Some("make -j4"));
+
+        let args = parse(&["--batch", "--export=errors.err", "make"]).unwrap();
+        assert_eq!(args.export, Some(PathBuf::from("errors.err")));
+
+        let args = parse(&["--shell", "/bin/bash", "--", "grep", "-rn", "foo bar"]).unwrap();
+        assert_eq!(args.shell, Some(PathBuf::from("/bin/bash")));
+        assert_eq!(args.cmdline().as_deref(), Some("argv: grep -rn 'foo bar'"));
+
+        assert!(parse(&["--frobnicate"]).is_err());
+        assert!(parse(&["make", "-j4"]).is_err());
+        assert!(parse(&["make", "--", "ls"]).is_err());
+        assert!(parse(&["--"]).is_err());
+    }
+}
@@ -0,0 +1,319 @@
+use super::*;
+use os_pipe::pipe;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum Format {
+    Text,
+    /// One JSON object per match per line (aka JSON Lines)
+    Json,
+}
+
+/// A record of the output that matched the regex. The lines of a
+/// multi-line record are joined with `\n`
+pub struct Match {
+    pub line: String,
+    /// `None` for the groups that did not participate in the match
+    pub captures: Vec<Option<String>>,
+    pub severity: Option<Severity>,
+    pub cmdline: Option<String>,
+}
+
+impl Match {
+    pub fn from_record(record: &Record, cmd: Option<&str>, shell: &shell::Shell) -> Option<Self> {
+        let line = record.text.as_str();
+        let regex = record.regex;
+        let cmd = record.cmd.or(cmd);
+        let caps = regex.captures(line.as_bytes()).ok()??;
+        let captures = (1..caps.len())
+            .map(|i| {
+                caps.get(i)
+                    .and_then(|mat| line.get(mat.start()..mat.end()))
+                    .map(String::from)
+            })
+            .collect();
+        Some(Self {
+            line: line.to_string(),
+            captures,
+            severity: record.severity,
+            cmdline: cmd.and_then(|cmd| render_cmdline(&record.raw, cmd, regex, shell)?.ok()),
+        })
+    }
+
+    pub fn write<F: Write>(&self, stream: &mut F, format: Format) -> io::Result<()> {
+        match format {
+            Format::Text => {
+                writeln!(stream, "{}", self.line)?;
+                for (i, capture) in self.captures.iter().enumerate() {
+                    if let Some(capture) = capture {
+                        writeln!(stream, "    \\{}: {}", i + 1, capture)?;
+                    }
+                }
+                if let Some(severity) = self.severity {
+                    writeln!(stream, "    severity: {}", severity)?;
+                }
+                if let Some(cmdline) = &self.cmdline {
+                    writeln!(stream, "    cmdline: {}", cmdline)?;
+                }
+            }
+            Format::Json => {
+                let captures = self
+                    .captures
+                    .iter()
+                    .map(|capture| {
+                        capture
+                            .as_deref()
+                            .map(export::json_string)
+                            .unwrap_or_else(|| "null".to_string())
+                    })
+                    .collect::<Vec<String>>()
+                    .join(",");
+                writeln!(
+                    stream,
+                    "{{\"line\":{},\"captures\":[{}],\"severity\":{},\"cmdline\":{}}}",
+                    export::json_string(&self.line),
+                    captures,
+                    self.severity
+                        .map(|severity| export::json_string(&severity.to_string()))
+                        .unwrap_or_else(|| "null".to_string()),
+                    self.cmdline
+                        .as_deref()
+                        .map(export::json_string)
+                        .unwrap_or_else(|| "null".to_string())
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Prints every line of the `input` that matches the `regex` in the
+/// `format`. The records are also collected into the `entries` if there
+/// are any.
+fn scan<R: Read>(
+    input: R,
+    matcher: &Matcher,
+    cmd: Option<&str>,
+    shell: &shell::Shell,
+    format: Format,
+    mut entries: Option<&mut Vec<QuickfixEntry>>,
+) {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let mut reader = BufReader::new(input);
+    let mut parser = ansi::Parser::new(8);
+    let mut buffer = Vec::new();
+    let mut pending = VecDeque::new();
+    let mut eof = false;
+    // NOTE: the index of the first pending line in the whole input
+    let mut index = 0;
+    loop {
+        // NOTE: a line is matched only once the lines a multi-line record
+        // starting at it may span are read, so the output is delayed by
+        // the window rather than buffered as a whole
+        while !eof && pending.len() < matcher.window() {
+            buffer.clear();
+            match reader.read_until(b'\n', &mut buffer) {
+                Ok(0) | Err(_) => eof = true,
+                Ok(_) => {
+                    let (mut text, _) = parser.parse_line(&buffer);
+                    if text.last() == Some(&b'\n') {
+                        text.pop();
+                    }
+                    pending.push_back(OutputLine::from_bytes(text, Vec::new(), None));
+                }
+            }
+        }
+        if pending.is_empty() {
+            break;
+        }
+
+        let lines = pending.make_contiguous();
+        let len = match matcher.find_record(lines, 0) {
+            Some(record) => {
+                if let Some(entries) = entries.as_mut() {
+                    entries.push(QuickfixEntry::from_record(&record, index));
+                }
+                let mat = Match::from_record(&record, cmd, shell);
+                // NOTE: the reader of our output may go away (`| head`)
+                if mat
+                    .map_or(Ok(()), |mat| mat.write(&mut stdout, format))
+                    .is_err()
+                {
+                    break;
+                }
+                record.len
+            }
+            None => 1,
+        };
+        pending.drain(..len);
+        index += len;
+    }
+}
+
+/// Runs the `cmdline` without the TUI, applies the current regex of the
+/// `profile` to its output (both stdout and stderr) and prints every
+/// match in the `format`. If there is no `cmdline` the `files` are
+/// scanned instead, and if there are no `files` the stdin of cm is.
+/// Returns the exit code of cm: the exit code of the child or 2 if cm
+/// could not do its job. The matched locations are written to the
+/// `export` file at the end. See [export::save](../export/fn.save.html)
+///
+/// NOTE: the matches are printed as soon as the lines come, so
+/// [RegexSelection::Best](enum.RegexSelection.html#variant.Best) has
+/// nothing to choose from and the current regex is used instead.
+pub fn run(
+    cmdline: Option<&str>,
+    files: &[PathBuf],
+    shell: &shell::Shell,
+    profile: &Profile,
+    format: Format,
+    export: Option<&Path>,
+) -> i32 {
+    let matcher = match profile.matcher() {
+        Some(Ok(matcher)) => matcher,
+        Some(Err(err)) => {
+            eprintln!("cm: invalid regex: {}", err);
+            return 2;
+        }
+        None => {
+            eprintln!("cm: no regex is selected in the configuration");
+            return 2;
+        }
+    };
+    let cmd = profile.current_cmd();
+    if let (Some(Ok(regex)), Some(cmd)) = (profile.current_regex(), &cmd) {
+        if let Err(err) = template::Template::parse(cmd, &regex) {
+            eprintln!("cm: invalid command template: {}", err);
+            return 2;
+        }
+    }
+
+    let mut entries = export.map(|_| Vec::new());
+    let code = scan_cmdline(
+        cmdline,
+        files,
+        shell,
+        &matcher,
+        cmd.as_deref(),
+        format,
+        entries.as_mut(),
+    );
+    if let (Some(path), Some(entries)) = (export, entries) {
+        if let Err(err) = export::save(path, &sort_entries(entries)) {
+            eprintln!("cm: {}", err);
+            return 2;
+        }
+    }
+    code
+}
+
+/// Scans the output of the `cmdline`, the `files` or the stdin. See
+/// [run](fn.run.html)
+fn scan_cmdline(
+    cmdline: Option<&str>,
+    files: &[PathBuf],
+    shell: &shell::Shell,
+    matcher: &Matcher,
+    cmd: Option<&str>,
+    format: Format,
+    mut entries: Option<&mut Vec<QuickfixEntry>>,
+) -> i32 {
+    let cmdline = match cmdline {
+        Some(cmdline) => cmdline,
+        None if !files.is_empty() => {
+            let mut code = 0;
+            for path in files {
+                match File::open(path) {
+                    Ok(file) => scan(file, matcher, cmd, shell, format, entries.as_deref_mut()),
+                    Err(err) => {
+                        eprintln!("cm: could not open {}: {}", path.display(), err);
+                        code = 2;
+                    }
+                }
+            }
+            return code;
+        }
+        None => {
+            scan(io::stdin(), matcher, cmd, shell, format, entries);
+            return 0;
+        }
+    };
+
+    let (reader, writer) = match pipe() {
+        Ok(pipe) => pipe,
+        Err(err) => {
+            eprintln!("cm: could not create a pipe: {}", err);
+            return 2;
+        }
+    };
+    let mut command = match shell.command(cmdline) {
+        Ok(command) => command,
+        Err(err) => {
+            eprintln!("cm: {}", err);
+            return 2;
+        }
+    };
+    match writer.try_clone() {
+        Ok(stderr_writer) => command.stderr(stderr_writer),
+        Err(err) => {
+            eprintln!("cm: could not create a pipe: {}", err);
+            return 2;
+        }
+    };
+    command.stdout(writer);
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(err) => {
+            eprintln!("cm: could not run `{}`: {}", cmdline, err);
+            return 2;
+        }
+    };
+    // NOTE: the writing ends must be closed on our side, otherwise we
+    // never get the EOF
+    drop(command);
+
+    scan(reader, matcher, cmd, shell, format, entries);
+
+    match child.wait() {
+        Ok(status) => status
+            .code()
+            .unwrap_or_else(|| 128 + status.signal().unwrap_or(0)),
+        Err(err) => {
+            eprintln!("cm: error waiting for `{}`: {}", cmdline, err);
+            2
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pcre2::bytes::RegexBuilder;
+
+    #[test]
+    fn test_json_match() {
+        let regex = RegexBuilder::new()
+            .utf(true)
+            .build(r"(\S+):(\d+):(x)?")
+            .unwrap();
+        let shell = shell::Shell::default();
+        let matcher = Matcher::single(regex);
+        let record = matcher
+            .find_record(&["src/\"a\".rs:42: oops\t"], 0)
+            .unwrap();
+        let mat = Match::from_record(&record, Some("vim +\\2 \\1"), &shell).unwrap();
+        let mut output = Vec::new();
+        mat.write(&mut output, Format::Json).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"line\":\"src/\\\"a\\\".rs:42: oops\\t\",\"captures\":[\"src/\\\"a\\\".rs\",\"42\",null],\"severity\":null,\"cmdline\":\"vim +42 'src/\\\"a\\\".rs'\"}\n"
+        );
+
+        assert!(matcher.find_record(&["no match here"], 0).is_none());
+    }
+}
@@ -0,0 +1,327 @@
+use super::*;
+
+/// A bundled regex for the locations reported by a well known tool.
+/// Every regex captures the `file` and the `line` and, if the tool
+/// reports it, the `col`, so the same [COMMANDS](constant.COMMANDS.html)
+/// work for all of them. The regexes of the tools that report the
+/// severity capture it into the [SEVERITY_GROUP](../constant.SEVERITY_GROUP.html).
+pub struct Entry {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub regex: &'static str,
+    /// the severity of the matches that do not capture one
+    pub severity: Option<Severity>,
+}
+
+pub const ENTRIES: [Entry; 9] = [
+    Entry {
+        name: "rustc",
+        description: "rustc and cargo: --> src/main.rs:4:5",
+        regex: r"^\s*--> (?<file>[^:\s]+):(?<line>\d+):(?<col>\d+)",
+        severity: None,
+    },
+    Entry {
+        name: "gcc",
+        description: "gcc and clang: main.c:3:10: error: ...",
+        regex: r"^(?<file>[^:\s][^:]*):(?<line>\d+):(?<col>\d+): (?<severity>(?:fatal )?error|warning|note):",
+        severity: None,
+    },
+    Entry {
+        name: "go",
+        description: "go build, vet and test: ./main.go:12:2: ...",
+        regex: r"^\s*(?<file>[^:\s]+\.go):(?<line>\d+):(?:(?<col>\d+):)?",
+        severity: None,
+    },
+    Entry {
+        name: "python",
+        description: "Python tracebacks: File \"app.py\", line 3, in main",
+        regex: r#"^\s*File "(?<file>[^"]+)", line (?<line>\d+)"#,
+        severity: Some(Severity::Error),
+    },
+    Entry {
+        name: "typescript",
+        description: "tsc: src/app.ts(12,5): error TS2322: ... or src/app.ts:12:5 - error ...",
+        regex: r"^(?<file>[^\s():]+\.[cm]?[jt]sx?)(?|\((?<line>\d+),(?<col>\d+)\): |:(\d+):(\d+) - )(?<severity>error|warning)",
+        severity: None,
+    },
+    Entry {
+        name: "eslint",
+        description: "eslint --format compact: app.js: line 1, col 7, Error - ...",
+        regex: r"^(?<file>[^:]+): line (?<line>\d+), col (?<col>\d+), (?<severity>Error|Warning) -",
+        severity: None,
+    },
+    Entry {
+        name: "pytest",
+        description: "pytest tracebacks: tests/test_app.py:7: AssertionError",
+        regex: r"^(?<file>[^\s:]+\.py):(?<line>\d+): \w+",
+        severity: Some(Severity::Error),
+    },
+    Entry {
+        name: "javac",
+        description: "javac: src/Main.java:5: error: cannot find symbol",
+        regex: r"^(?<file>[^\s:]+\.java):(?<line>\d+): (?<severity>error|warning):",
+        severity: None,
+    },
+    Entry {
+        name: "grep",
+        description: "grep -n and rg -n/--vimgrep: src/main.rs:42:fn main() {",
+        regex: r"^(?<file>[^:\s]+):(?<line>\d+):(?:(?<col>\d+):)?",
+        severity: None,
+    },
+];
+
+/// The commands of the rules made of the [ENTRIES](constant.ENTRIES.html)
+pub const COMMANDS: [&str; 2] = ["vim +{line} {file}", "emacs -nw +{line}:{col:-1} {file}"];
+
+pub fn find(name: &str) -> Option<&'static Entry> {
+    ENTRIES.iter().find(|entry| entry.name == name)
+}
+
+/// Makes the built-in rule of the entry called `name`
+pub fn rule(name: &str) -> Option<Rule> {
+    find(name).map(|entry| Rule {
+        name: entry.name.to_string(),
+        description: Some(entry.description.to_string()),
+        regex: entry.regex.to_string(),
+        cmds: COMMANDS.iter().map(|cmd| cmd.to_string()).collect(),
+        severity: entry.severity,
+        builtin: true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pcre2::bytes::{Regex, RegexBuilder};
+
+    fn regex(name: &str) -> Regex {
+        RegexBuilder::new()
+            .utf(true)
+            .ucp(true)
+            .build(find(name).unwrap().regex)
+            .unwrap()
+    }
+
+    /// Returns the `(file, line, col)` of every line of the `output` the
+    /// entry called `name` matches
+    fn locations(name: &str, output: &str) -> Vec<(String, String, Option<String>)> {
+        let regex = regex(name);
+        output
+            .lines()
+            .filter_map(|line| {
+                let caps = regex.captures(line.as_bytes()).unwrap()?;
+                let group = |name: &str| {
+                    caps.name(name)
+                        .map(|mat| line[mat.start()..mat.end()].to_string())
+                };
+                Some((group("file")?, group("line")?, group("col")))
+            })
+            .collect()
+    }
+
+    fn location(file: &str, line: &str, col: Option<&str>) -> (String, String, Option<String>) {
+        (file.to_string(), line.to_string(), col.map(String::from))
+    }
+
+    #[test]
+    fn test_entries() {
+        for entry in ENTRIES.iter() {
+            let regex = regex(entry.name);
+            let names = regex.capture_names();
+            assert!(names.contains(&Some("file".to_string())), "{}", entry.name);
+            assert!(names.contains(&Some("line".to_string())), "{}", entry.name);
+            // NOTE: the values of cm.conf are trimmed, so the regex would
+            // not survive saving and loading the configuration
+            assert_eq!(entry.regex.trim(), entry.regex, "{}", entry.name);
+        }
+    }
+
+    #[test]
+    fn test_rustc() {
+        let output = r#"   Compiling cm v0.1.0 (/home/user/cm)
+error[E0308]: mismatched types
+ --> src/main.rs:4:18
+  |
+4 |     let x: i32 = "oops";
+  |            ---   ^^^^^^ expected `i32`, found `&str`
+  |            |
+  |            expected due to this
+
+warning: unused variable: `y`
+   --> src/cm/output_buffer.rs:120:9
+    |
+120 |     let y = 5;
+    |         ^ help: if this is intentional, prefix it with an underscore: `_y`
+"#;
+        assert_eq!(
+            locations("rustc", output),
+            vec![
+                location("src/main.rs", "4", Some("18")),
+                location("src/cm/output_buffer.rs", "120", Some("9")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gcc() {
+        let output = r#"main.c: In function 'main':
+main.c:3:10: error: expected ';' before '}' token
+    3 |   return 0
+      |          ^
+      |          ;
+    4 | }
+      | ~
+src/lib/util.c:12:5: warning: unused variable 'x' [-Wunused-variable]
+foo.c:1:10: fatal error: bar.h: No such file or directory
+src/a.cpp:7:3: note: candidate function not viable
+In file included from main.c:1:
+1 error generated.
+"#;
+        assert_eq!(
+            locations("gcc", output),
+            vec![
+                location("main.c", "3", Some("10")),
+                location("src/lib/util.c", "12", Some("5")),
+                location("foo.c", "1", Some("10")),
+                location("src/a.cpp", "7", Some("3")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_go() {
+        let output = r#"# example.com/app
+./main.go:12:2: undefined: foo
+./main.go:15:6: declared and not used: x
+--- FAIL: TestAdd (0.00s)
+    math_test.go:15: expected 1, got 2
+FAIL
+"#;
+        assert_eq!(
+            locations("go", output),
+            vec![
+                location("./main.go", "12", Some("2")),
+                location("./main.go", "15", Some("6")),
+                location("math_test.go", "15", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_python() {
+        let output = r#"Traceback (most recent call last):
+  File "/home/user/app.py", line 10, in <module>
+    main()
+  File "/home/user/app.py", line 6, in main
+    json.loads("{")
+  File "/usr/lib/python3.11/json/__init__.py", line 346, in loads
+    return _default_decoder.decode(s)
+json.decoder.JSONDecodeError: Expecting property name enclosed in double quotes: line 1 column 2 (char 1)
+"#;
+        assert_eq!(
+            locations("python", output),
+            vec![
+                location("/home/user/app.py", "10", None),
+                location("/home/user/app.py", "6", None),
+                location("/usr/lib/python3.11/json/__init__.py", "346", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_typescript() {
+        let output = r#"src/app.ts(12,5): error TS2322: Type 'string' is not assignable to type 'number'.
+src/components/Button.tsx:3:10 - error TS2305: Module '"react"' has no exported member 'Foo'.
+
+3 import { Foo } from "react";
+           ~~~
+
+Found 2 errors in 2 files.
+"#;
+        assert_eq!(
+            locations("typescript", output),
+            vec![
+                location("src/app.ts", "12", Some("5")),
+                location("src/components/Button.tsx", "3", Some("10")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_eslint() {
+        let output = r#"/home/user/app/src/index.js: line 1, col 7, Error - 'x' is assigned a value but never used. (no-unused-vars)
+/home/user/app/src/util.js: line 22, col 13, Warning - Unexpected console statement. (no-console)
+
+2 problems
+"#;
+        assert_eq!(
+            locations("eslint", output),
+            vec![
+                location("/home/user/app/src/index.js", "1", Some("7")),
+                location("/home/user/app/src/util.js", "22", Some("13")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pytest() {
+        let output = r#"============================= test session starts ==============================
+collected 2 items
+
+tests/test_math.py .F                                                    [100%]
+
+=================================== FAILURES ===================================
+___________________________________ test_add ___________________________________
+
+    def test_add():
+>       assert add(1, 1) == 3
+E       assert 2 == 3
+
+tests/test_math.py:7: AssertionError
+=========================== short test summary info ============================
+FAILED tests/test_math.py::test_add - assert 2 == 3
+========================= 1 failed, 1 passed in 0.02s ==========================
+"#;
+        assert_eq!(
+            locations("pytest", output),
+            vec![location("tests/test_math.py", "7", None)]
+        );
+    }
+
+    #[test]
+    fn test_javac() {
+        let output = r#"src/Main.java:5: error: cannot find symbol
+        System.out.println(foo);
+                           ^
+  symbol:   variable foo
+  location: class Main
+src/Util.java:12: warning: [deprecation] Date(String) in Date has been deprecated
+1 error
+1 warning
+"#;
+        assert_eq!(
+            locations("javac", output),
+            vec![
+                location("src/Main.java", "5", None),
+                location("src/Util.java", "12", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_grep() {
+        let output = r#"src/main.rs:42:fn main() {
+src/cm/shell.rs:7:pub const ARGV_PREFIX: &str = "argv:";
+src/cm/tab.rs:183:5:    pub fn jump_to_next_match<F: Fn(&OutputLine) -> bool>(&mut self, matches: F) {
+Binary file target/debug/cm matches
+"#;
+        assert_eq!(
+            locations("grep", output),
+            vec![
+                location("src/main.rs", "42", None),
+                location("src/cm/shell.rs", "7", None),
+                location("src/cm/tab.rs", "183", Some("5")),
+            ]
+        );
+    }
+}
@@ -0,0 +1,259 @@
+use super::*;
+use libc::*;
+use std::fs::File;
+use std::io::{BufRead, BufReader, ErrorKind, Seek, SeekFrom};
+use std::os::unix::io::AsRawFd;
+
+// TODO(#94): mark_nonblocking does not work on Windows
+fn mark_nonblocking<Fd: AsRawFd>(fd: &mut Fd) {
+    unsafe {
+        let flags = libc::fcntl(fd.as_raw_fd(), F_GETFL, 0);
+        libc::fcntl(fd.as_raw_fd(), F_SETFL, flags | O_NONBLOCK);
+    }
+}
+
+/// One of the output streams of the currently running child
+pub struct ChildStream {
+    pub stream: Stream,
+    pub reader: BufReader<File>,
+    /// the beginning of the line that was not terminated with `\n` yet
+    pub pending: Vec<u8>,
+    /// index of the line in the OutputList that displays the pending
+    /// buffer. The line is updated in place until the `\n` arrives.
+    pub open_line: Option<usize>,
+    /// keeps track of the colors of the output of the stream
+    pub ansi_parser: ansi::Parser,
+    /// the stream reached its end (or broke)
+    pub eof: bool,
+    /// do not stop at the end of the stream, wait for more data to be
+    /// appended instead (`tail -f`). Only makes sense for regular files.
+    pub follow: bool,
+}
+
+impl ChildStream {
+    pub fn new(stream: Stream, mut file: File, tabsize: usize) -> Self {
+        mark_nonblocking(&mut file);
+        Self {
+            stream,
+            reader: BufReader::new(file),
+            pending: Vec::new(),
+            open_line: None,
+            ansi_parser: ansi::Parser::new(tabsize),
+            eof: false,
+            follow: false,
+        }
+    }
+
+    /// Everything before the last `\r` of the pending buffer is going to
+    /// be cleared by the [ansi::Parser](ansi/struct.Parser.html) anyway.
+    /// So we drop it to not reparse the whole history of a progress bar
+    /// on each update. Only the style changes are kept track of.
+    fn drop_overwritten(&mut self) {
+        let len = self.pending.len();
+        if let Some(pos) = self
+            .pending
+            .iter()
+            .rposition(|&b| b == b'\r')
+            .filter(|&pos| pos + 1 < len && self.pending[pos + 1] != b'\n')
+        {
+            self.ansi_parser.parse_line(&self.pending[..pos]);
+            self.pending.drain(..=pos);
+        }
+    }
+
+    fn put_line(&mut self, list: &mut OutputList, line: OutputLine) {
+        match self.open_line {
+            Some(index) if index < list.lines.len() => list.replace(index, line),
+            _ => list.push(line),
+        }
+    }
+
+    pub fn push_pending(&mut self, list: &mut OutputList) {
+        self.drop_overwritten();
+        // TODO(#185): move the tab expansion to ItemList so it's available for every list-like component
+        let (bytes, spans) = self.ansi_parser.parse_line(&self.pending);
+        self.put_line(
+            list,
+            OutputLine::from_bytes(bytes, spans, Some(self.stream)),
+        );
+        self.open_line = None;
+        self.pending.clear();
+    }
+
+    /// Displays the pending buffer without waiting for the `\n`. The
+    /// style changes are not committed to the
+    /// [ChildStream::ansi_parser](struct.ChildStream.html#structfield.ansi_parser)
+    /// because the same pending buffer is going to be parsed again.
+    fn update_open_line(&mut self, list: &mut OutputList) {
+        self.drop_overwritten();
+        let (bytes, spans) = self.ansi_parser.clone().parse_line(&self.pending);
+        self.put_line(
+            list,
+            OutputLine::from_bytes(bytes, spans, Some(self.stream)),
+        );
+        if self.open_line.is_none() {
+            self.open_line = Some(list.lines.len() - 1);
+        }
+    }
+
+    /// Reads at most `limit` lines from the stream into the `list`.
+    ///
+    /// The output is read as bytes, so the invalid UTF-8 does not stop
+    /// the reading. See [OutputLine::from_bytes](struct.OutputLine.html#method.from_bytes).
+    ///
+    /// The line that is not terminated with `\n` yet is displayed as well
+    /// and updated in place as the rest of it arrives. See
+    /// [ansi::Parser::parse_line](ansi/struct.Parser.html#method.parse_line)
+    /// for how `\r` is handled.
+    ///
+    /// Returns `true` if any lines were pushed or updated.
+    pub fn poll(&mut self, list: &mut OutputList, limit: usize) -> bool {
+        let mut changed = false;
+        let mut received = false;
+
+        for _ in 0..limit {
+            let pending_len = self.pending.len();
+            let result = self.reader.read_until(b'\n', &mut self.pending);
+            received = received || self.pending.len() > pending_len;
+            match result {
+                // NOTE: a followed file is simply not appended to yet,
+                // so it is the same as WouldBlock
+                Ok(0) if self.follow => {
+                    if received && !self.pending.is_empty() {
+                        self.update_open_line(list);
+                        changed = true;
+                    }
+                    break;
+                }
+                Ok(0) => {
+                    self.eof = true;
+                    // NOTE: the last line of the output may not be terminated with `\n`
+                    if !self.pending.is_empty() {
+                        self.push_pending(list);
+                        changed = true;
+                    }
+                    break;
+                }
+                Ok(_) => {
+                    if self.pending.ends_with(b"\n") {
+                        self.push_pending(list);
+                        changed = true;
+                    }
+                }
+                // NOTE: the beginning of the line stays in the pending
+                // buffer until the rest of the line arrives
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                    if received && !self.pending.is_empty() {
+                        self.update_open_line(list);
+                        changed = true;
+                    }
+                    break;
+                }
+                // NOTE: the master of a pseudo-terminal returns EIO after
+                // the child closes the slave, which is basically EOF
+                Err(_) => {
+                    self.eof = true;
+                    if !self.pending.is_empty() {
+                        self.push_pending(list);
+                        changed = true;
+                    }
+                    break;
+                }
+            }
+        }
+
+        changed
+    }
+
+    /// Checks if the followed file was truncated (the logs are usually
+    /// rotated this way) and starts reading it from the beginning if so.
+    /// Returns `true` if the file was truncated.
+    pub fn rewind_if_truncated(&mut self) -> bool {
+        let file = self.reader.get_mut();
+        let truncated = match (file.metadata(), file.stream_position()) {
+            (Ok(metadata), Ok(position)) => metadata.len() < position,
+            _ => false,
+        };
+        if truncated {
+            // NOTE: seeking the BufReader discards its buffer as well
+            let _ = self.reader.seek(SeekFrom::Start(0));
+            self.pending.clear();
+            self.open_line = None;
+        }
+        truncated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use os_pipe::pipe;
+    use pcre2::bytes::RegexBuilder;
+    use std::io::Write;
+    use std::os::unix::io::{FromRawFd, IntoRawFd};
+
+    #[test]
+    fn test_poll_non_utf8_output() {
+        let (reader, mut writer) = pipe().unwrap();
+        let mut stream = ChildStream::new(
+            Stream::Stderr,
+            unsafe { File::from_raw_fd(reader.into_raw_fd()) },
+            8,
+        );
+        let mut list = OutputList::new();
+
+        // Latin-1
+        writer.write_all(b"caf\xe9.c:1: \xabquoted\xbb\n").unwrap();
+        // truncated multibyte sequence split between two writes
+        writer.write_all(b"\x1b[31m\xe2\x82").unwrap();
+        assert!(stream.poll(&mut list, 1024));
+        writer.write_all(b"\x1b[0m.c:2:\nno newline").unwrap();
+        drop(writer);
+        assert!(stream.poll(&mut list, 1024));
+
+        let lines = &list.list.items;
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].text, "caf\u{FFFD}.c:1: \u{FFFD}quoted\u{FFFD}\n");
+        assert_eq!(lines[0].as_bytes(), b"caf\xe9.c:1: \xabquoted\xbb\n");
+        assert_eq!(lines[1].text, "\u{FFFD}.c:2:\n");
+        assert_eq!(lines[1].as_bytes(), b"\xe2\x82.c:2:\n");
+        assert_eq!(lines[1].spans.len(), 1);
+        assert_eq!((lines[1].spans[0].start, lines[1].spans[0].end), (0, 3));
+        assert_eq!(lines[2].text, "no newline");
+        assert!(lines.iter().all(|line| line.stream == Some(Stream::Stderr)));
+
+        let regex = RegexBuilder::new()
+            .utf(true)
+            .ucp(true)
+            .build(r"(\/?\b.*?):(\d+):")
+            .unwrap();
+        assert!(lines[0].is_match(&regex));
+    }
+
+    #[test]
+    fn test_poll_progress_line() {
+        let (reader, mut writer) = pipe().unwrap();
+        let mut stream = ChildStream::new(
+            Stream::Stdout,
+            unsafe { File::from_raw_fd(reader.into_raw_fd()) },
+            8,
+        );
+        let mut list = OutputList::new();
+
+        writer.write_all(b"Compiling\n 10%").unwrap();
+        assert!(stream.poll(&mut list, 1024));
+        assert_eq!(list.list.items.len(), 2);
+        assert_eq!(list.list.items[1].text, " 10%");
+
+        writer.write_all(b"\r 50%\r").unwrap();
+        assert!(stream.poll(&mut list, 1024));
+        assert!(!stream.poll(&mut list, 1024));
+        assert_eq!(list.list.items.len(), 2);
+        assert_eq!(list.list.items[1].text, " 50%");
+
+        writer.write_all(b"\x1b[2K100%\nDone\n").unwrap();
+        assert!(stream.poll(&mut list, 1024));
+        let texts: Vec<&str> = list.lines.iter().map(|x| x.text.as_str()).collect();
+        assert_eq!(texts, vec!["Compiling\n", "100%\n", "Done\n"]);
+    }
+}
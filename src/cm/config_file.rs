@@ -0,0 +1,717 @@
+use super::*;
+use std::env::var;
+use std::fs::{self, copy, create_dir_all, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+pub const CONFIG_FILE_NAME: &str = "cm.conf";
+
+/// The configuration of a project. See [project_path](fn.project_path.html)
+pub const PROJECT_CONFIG_FILE_NAME: &str = ".cm.conf";
+
+/// How often the configuration file is checked for the changes made by
+/// another cm or by hand
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// The lines of the configuration without the version line the way
+/// [Profile::to_file](struct.Profile.html#method.to_file) writes them, so
+/// a file edited by hand is compared by its settings rather than by its
+/// formatting
+fn settings_lines(profile: &Profile) -> Vec<String> {
+    let mut buffer = Vec::new();
+    profile
+        .to_file(&mut buffer)
+        .expect("Could not write the configuration into memory");
+    String::from_utf8_lossy(&buffer)
+        .lines()
+        .skip(1)
+        .map(String::from)
+        .collect()
+}
+
+/// The name of the setting the `line` belongs to. All of the rules and
+/// all of the keys of an action are merged as a whole.
+fn setting(line: &str) -> String {
+    match config::split_key_value(line) {
+        Some((key, _)) if key == "builtin_rule" || key.starts_with("rule") => "rules".to_string(),
+        Some((key, action)) if key.starts_with("key:") => format!("keys of {}", action),
+        Some((key, _)) => key.to_string(),
+        None => line.to_string(),
+    }
+}
+
+/// The cursors of the lists move with every pick, so they are the
+/// user's own and are only read from and saved to the user file
+fn is_cursor(name: &str) -> bool {
+    name == "current_regex" || name == "current_cmd"
+}
+
+/// The settings a project file may set. It comes with the checkout, so it
+/// does not get to pick the shell, the keys or anything else that decides
+/// how and when cm runs commands.
+fn is_project_setting(name: &str) -> bool {
+    name == "regexs" || name == "cmds" || name == "rules"
+}
+
+/// The non-empty lines of the configuration without the surrounding
+/// whitespace
+fn settings_of(lines: Vec<String>) -> Vec<String> {
+    lines
+        .into_iter()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+/// Groups the `lines` by their [setting](fn.setting.html) in the order
+/// the settings first appear in
+fn group(lines: &[String]) -> Vec<(String, Vec<String>)> {
+    let mut result: Vec<(String, Vec<String>)> = Vec::new();
+    for line in lines.iter() {
+        let name = setting(line);
+        match result.iter_mut().find(|(other, _)| *other == name) {
+            Some((_, group)) => group.push(line.clone()),
+            None => result.push((name, vec![line.clone()])),
+        }
+    }
+    result
+}
+
+/// The lines of the setting `name` in the `groups` made by
+/// [group](fn.group.html)
+fn find_group(groups: &[(String, Vec<String>)], name: &str) -> Vec<String> {
+    groups
+        .iter()
+        .find(|(other, _)| other == name)
+        .map(|(_, group)| group.clone())
+        .unwrap_or_default()
+}
+
+/// Three-way merge of the settings: a setting that was changed since the
+/// `base` only on one side is taken from that side. Returns the merged
+/// lines and the names of the settings that were changed differently on
+/// both sides. Those are taken from `ours`.
+fn merge(base: &[String], ours: &[String], theirs: &[String]) -> (Vec<String>, Vec<String>) {
+    let (base, ours, theirs) = (group(base), group(ours), group(theirs));
+
+    let mut names: Vec<&String> = ours.iter().map(|(name, _)| name).collect();
+    for (name, _) in theirs.iter() {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    let mut merged = Vec::new();
+    let mut conflicts = Vec::new();
+    for name in names {
+        let (base, ours, theirs) = (
+            find_group(&base, name),
+            find_group(&ours, name),
+            find_group(&theirs, name),
+        );
+        if ours == base {
+            merged.extend(theirs);
+        } else {
+            if theirs != base && theirs != ours {
+                conflicts.push(name.clone());
+            }
+            merged.extend(ours);
+        }
+    }
+    (merged, conflicts)
+}
+
+/// Where a setting comes from. The later layers take precedence over the
+/// earlier ones.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Layer {
+    /// `cm.conf` in `$XDG_CONFIG_DIRS` shared by all the users of the
+    /// machine. cm never writes into it.
+    System,
+    /// `$XDG_CONFIG_HOME/cm.conf` or the one passed with `--config`
+    User,
+    /// `.cm.conf` of the project, usually committed into its repository.
+    /// It only sets the regexs, the cmds and the rules.
+    Project,
+}
+
+/// The first `cm.conf` in `$XDG_CONFIG_DIRS` (`/etc/xdg` by default)
+pub fn system_path() -> Option<PathBuf> {
+    let dirs = var("XDG_CONFIG_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/etc/xdg".to_string());
+    dirs.split(':')
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| Path::new(dir).join(CONFIG_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// The [PROJECT_CONFIG_FILE_NAME](constant.PROJECT_CONFIG_FILE_NAME.html)
+/// in the `dir` or the closest one above it
+pub fn project_path(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// Where the settings of the file at `path` are saved instead of it when
+/// saving them would throw away the changes made to the file
+pub fn conflict_path(path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.conflict", path.display()))
+}
+
+fn write_settings(path: &Path, lines: &[String]) -> io::Result<()> {
+    path.parent().map(create_dir_all);
+    let mut file = File::create(path)?;
+    writeln!(file, "version = {}", migration::CURRENT_VERSION)?;
+    for line in lines.iter() {
+        writeln!(file, "{}", line)?;
+    }
+    Ok(())
+}
+
+/// The settings of the file of the `layer` read from the `input`. Only
+/// the user file gets the defaults of the settings added since its
+/// version, the other files have only the settings they set. Returns the
+/// settings and the names of the ones the layer may not set.
+fn layer_settings(
+    layer: Layer,
+    path: &Path,
+    input: &str,
+) -> Result<(Vec<String>, Vec<String>), String> {
+    let lines = if layer == Layer::User {
+        migration::migrate(input)
+    } else {
+        migration::migrate_without_defaults(input)
+    }
+    .map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    let mut ignored = Vec::new();
+    let lines: Vec<String> = settings_of(lines)
+        .into_iter()
+        .filter(|line| {
+            let name = setting(line);
+            match layer {
+                Layer::User => true,
+                Layer::System => !is_cursor(&name),
+                Layer::Project if is_project_setting(&name) => true,
+                Layer::Project => {
+                    if !is_cursor(&name) && !ignored.contains(&name) {
+                        ignored.push(name);
+                    }
+                    false
+                }
+            }
+        })
+        .collect();
+    // NOTE: every file is checked on its own, so the errors point at the
+    // file they are in
+    Profile::parse(&lines, path)?;
+    Ok((lines, ignored))
+}
+
+/// A configuration file of a [Layer](enum.Layer.html) with the settings
+/// it had the last time it was read or written
+struct ConfigFile {
+    layer: Layer,
+    path: PathBuf,
+    lines: Vec<String>,
+    modified: Option<SystemTime>,
+    /// the file does not exist yet or is of an older version, so it is
+    /// written even if its settings have not changed
+    outdated: bool,
+}
+
+impl ConfigFile {
+    /// Returns the file and the names of the settings it may not set
+    fn load(layer: Layer, path: PathBuf) -> Result<(Self, Vec<String>), String> {
+        let modified = modified(&path);
+        let input = fs::read_to_string(&path)
+            .map_err(|err| format!("Could not read {}: {}", path.display(), err))?;
+        let version =
+            migration::version(&input).map_err(|err| format!("{}: {}", path.display(), err))?;
+        // NOTE: only the user file is rewritten just to bring it up to the
+        // current version, so only it is backed up
+        let outdated = layer == Layer::User && version != migration::CURRENT_VERSION;
+        if outdated && version < migration::CURRENT_VERSION {
+            let backup = format!("{}.bak", path.display());
+            copy(&path, &backup).map_err(|err| {
+                format!(
+                    "Could not back up {} to {}: {}",
+                    path.display(),
+                    backup,
+                    err
+                )
+            })?;
+        }
+        let (lines, ignored) = layer_settings(layer, &path, &input)?;
+        let file = Self {
+            layer,
+            path,
+            lines,
+            modified,
+            outdated,
+        };
+        Ok((file, ignored))
+    }
+
+    /// The file that could not be [load](#method.load)ed. It contributes
+    /// no settings until it is fixed and [reload](#method.reload)ed.
+    fn broken(layer: Layer, path: PathBuf) -> Self {
+        Self {
+            layer,
+            modified: modified(&path),
+            path,
+            lines: Vec::new(),
+            outdated: false,
+        }
+    }
+
+    /// Reads the file again if its modification time has changed or if
+    /// `force` is set. Returns `true` if its settings have changed.
+    fn reload(&mut self, force: bool) -> Result<bool, String> {
+        let modified = modified(&self.path);
+        if modified.is_none() || (!force && modified == self.modified) {
+            return Ok(false);
+        }
+        self.modified = modified;
+
+        let input = fs::read_to_string(&self.path)
+            .map_err(|err| format!("Could not read {}: {}", self.path.display(), err))?;
+        let (lines, _) = layer_settings(self.layer, &self.path, &input)?;
+        if lines == self.lines {
+            return Ok(false);
+        }
+        self.lines = lines;
+        Ok(true)
+    }
+}
+
+/// The configuration files the [Profile](struct.Profile.html) is loaded
+/// from and saved to, from the lowest [Layer](enum.Layer.html) to the
+/// highest. Every setting is taken from the highest file that has it and
+/// is saved back into that file. The changes made to the files by another
+/// cm or by hand while cm is running are merged into the
+/// [Profile](struct.Profile.html), so the last cm to exit does not
+/// silently win.
+pub struct ConfigLayers {
+    files: Vec<ConfigFile>,
+    /// the settings of all the files together as they were the last time
+    /// the files were read or written. Both the files and the
+    /// [Profile](struct.Profile.html) are compared against them to tell
+    /// who changed what.
+    base: Vec<String>,
+    checked: Instant,
+}
+
+impl ConfigLayers {
+    /// Loads the [Profile](struct.Profile.html) from the files that
+    /// exist. The initial settings fill in the settings that the system
+    /// file does not have if there is no user file yet. A file that can
+    /// not be loaded is skipped. Returns the errors of such files and of
+    /// the settings a project file may not set.
+    pub fn load(
+        system: Option<PathBuf>,
+        user: PathBuf,
+        project: Option<PathBuf>,
+    ) -> (Self, Profile, Vec<String>) {
+        let mut errors = Vec::new();
+        let mut load = |layer, path: PathBuf| match ConfigFile::load(layer, path.clone()) {
+            Ok((file, ignored)) => {
+                if !ignored.is_empty() {
+                    errors.push(format!(
+                        "{}: ignored {}. A project file only sets regexs, cmds and rules",
+                        file.path.display(),
+                        ignored.join(", ")
+                    ));
+                }
+                file
+            }
+            Err(err) => {
+                errors.push(err);
+                ConfigFile::broken(layer, path)
+            }
+        };
+
+        let mut files = Vec::new();
+        if let Some(path) = system.filter(|path| *path != user) {
+            files.push(load(Layer::System, path));
+        }
+        if user.exists() {
+            files.push(load(Layer::User, user.clone()));
+        } else {
+            let system = files
+                .first()
+                .map(|file| group(&file.lines))
+                .unwrap_or_default();
+            let lines = group(&settings_lines(&Profile::initial()))
+                .into_iter()
+                .filter(|(name, _)| system.iter().all(|(other, _)| other != name))
+                .flat_map(|(_, lines)| lines)
+                .collect();
+            files.push(ConfigFile {
+                layer: Layer::User,
+                path: user.clone(),
+                lines,
+                modified: None,
+                outdated: true,
+            });
+        }
+        if let Some(path) = project.filter(|path| files.iter().all(|file| file.path != *path)) {
+            files.push(load(Layer::Project, path));
+        }
+
+        let mut result = Self {
+            files,
+            base: Vec::new(),
+            checked: Instant::now(),
+        };
+        let profile = Profile::parse(&result.compose(), &user).unwrap_or_else(|err| {
+            errors.push(err);
+            Profile::initial()
+        });
+        result.base = settings_lines(&profile);
+        (result, profile, errors)
+    }
+
+    fn user_file(&self) -> usize {
+        self.files
+            .iter()
+            .position(|file| file.layer == Layer::User)
+            .expect("There is always a user file")
+    }
+
+    /// The settings of all the files together
+    fn compose(&self) -> Vec<String> {
+        let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+        for file in self.files.iter() {
+            for (name, lines) in group(&file.lines) {
+                match groups.iter_mut().find(|(other, _)| *other == name) {
+                    Some((_, group)) => *group = lines,
+                    None => groups.push((name, lines)),
+                }
+            }
+        }
+        groups.into_iter().flat_map(|(_, lines)| lines).collect()
+    }
+
+    /// The index of the highest file that has the setting `name`
+    fn origin(&self, name: &str) -> Option<usize> {
+        self.files
+            .iter()
+            .rposition(|file| group(&file.lines).iter().any(|(other, _)| other == name))
+    }
+
+    /// Splits the settings of the [Profile](struct.Profile.html) between
+    /// the files: a changed setting goes back into the file it came from,
+    /// the settings that are new or came from the system file and the
+    /// cursors of the lists go into the user file. The rest of the
+    /// settings stay the way they are written in the files.
+    fn split(&self, ours: &[String]) -> Vec<Vec<String>> {
+        let base = group(&self.base);
+        let ours = group(ours);
+        let mut result: Vec<Vec<(String, Vec<String>)>> =
+            self.files.iter().map(|file| group(&file.lines)).collect();
+
+        for (name, lines) in ours.iter() {
+            if find_group(&base, name) == *lines {
+                continue;
+            }
+            let index = match self.origin(name) {
+                Some(index) if self.files[index].layer != Layer::System && !is_cursor(name) => {
+                    index
+                }
+                _ => self.user_file(),
+            };
+            match result[index].iter_mut().find(|(other, _)| other == name) {
+                Some((_, group)) => *group = lines.clone(),
+                None => result[index].push((name.clone(), lines.clone())),
+            }
+        }
+
+        for (name, _) in base.iter() {
+            if ours.iter().all(|(other, _)| other != name) {
+                if let Some(index) = self
+                    .origin(name)
+                    .filter(|index| self.files[*index].layer != Layer::System)
+                {
+                    result[index].retain(|(other, _)| other != name);
+                }
+            }
+        }
+
+        result
+            .into_iter()
+            .map(|groups| groups.into_iter().flat_map(|(_, lines)| lines).collect())
+            .collect()
+    }
+
+    /// Reads the files that were modified again. Returns the indices of
+    /// the files whose settings have changed and the errors of the files
+    /// that could not be read.
+    fn reload(&mut self, force: bool) -> (Vec<usize>, Vec<(usize, String)>) {
+        let mut changed = Vec::new();
+        let mut errors = Vec::new();
+        for (index, file) in self.files.iter_mut().enumerate() {
+            match file.reload(force) {
+                Ok(true) => changed.push(index),
+                Ok(false) => {}
+                Err(err) => errors.push((index, err)),
+            }
+        }
+        (changed, errors)
+    }
+
+    /// Merges the changes made to the files since they were read into the
+    /// `profile`. Returns the settings that were changed both in the
+    /// files and in the `profile` or `None` if the settings of the files
+    /// together have not changed.
+    fn merge_into(&mut self, profile: &mut Profile) -> Result<Option<Vec<String>>, String> {
+        let user = self.files[self.user_file()].path.clone();
+        let theirs = settings_lines(&Profile::parse(&self.compose(), &user)?);
+        if theirs == self.base {
+            return Ok(None);
+        }
+
+        let ours = settings_lines(profile);
+        let (merged, conflicts) = merge(&self.base, &ours, &theirs);
+        if merged != ours {
+            let mut merged = Profile::parse(&merged, &user)?;
+            merged.regex_override = profile.regex_override.take();
+            merged.cmd_override = profile.cmd_override.take();
+            *profile = merged;
+        }
+        self.base = theirs;
+        Ok(Some(conflicts))
+    }
+
+    /// Checks the files every [POLL_INTERVAL](constant.POLL_INTERVAL.html)
+    /// and merges their changes into the `profile`. Returns the message
+    /// for the status line if something was merged.
+    pub fn poll(&mut self, profile: &mut Profile) -> Option<Result<String, String>> {
+        // NOTE: the lists are not replaced while the user is editing them
+        if self.checked.elapsed() < POLL_INTERVAL
+            || profile.regex_list.state != StringListState::Navigate
+            || profile.cmd_list.state != StringListState::Navigate
+        {
+            return None;
+        }
+        self.checked = Instant::now();
+
+        let (changed, errors) = self.reload(false);
+        if let Some((_, err)) = errors.into_iter().next() {
+            return Some(Err(err));
+        }
+        if changed.is_empty() {
+            return None;
+        }
+
+        let paths = changed
+            .iter()
+            .map(|index| self.files[*index].path.display().to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        match self.merge_into(profile) {
+            Ok(None) => None,
+            Ok(Some(conflicts)) if conflicts.is_empty() => Some(Ok(format!("Reloaded {}", paths))),
+            Ok(Some(conflicts)) => Some(Err(format!(
+                "{} changed on disk. Kept the {} of this cm",
+                paths,
+                conflicts.join(", ")
+            ))),
+            Err(err) => Some(Err(err)),
+        }
+    }
+
+    /// Saves every setting of the `profile` into the file it came from
+    /// after merging the changes made to the files since they were read.
+    /// A file that can not be read or whose settings were changed both in
+    /// the file and in the `profile` is left alone and its settings are
+    /// saved into the [conflict_path](fn.conflict_path.html) instead.
+    /// Returns the errors.
+    pub fn save(&mut self, profile: &mut Profile) -> Vec<String> {
+        let (_, mut conflicts) = self.reload(true);
+        match self.merge_into(profile) {
+            Ok(Some(names)) => {
+                for name in names {
+                    let index = self.origin(&name).unwrap_or_else(|| self.user_file());
+                    conflicts.push((
+                        index,
+                        format!("{} changed both in the file and in this cm", name),
+                    ));
+                }
+            }
+            Ok(None) => {}
+            Err(err) => return vec![err],
+        }
+
+        let mut errors = Vec::new();
+        let split = self.split(&settings_lines(profile));
+        for (index, (file, lines)) in self.files.iter_mut().zip(split).enumerate() {
+            let reasons = conflicts
+                .iter()
+                .filter(|(other, _)| *other == index)
+                .map(|(_, reason)| reason.as_str())
+                .collect::<Vec<&str>>();
+            if file.layer == Layer::System {
+                errors.extend(reasons.iter().map(|reason| reason.to_string()));
+                continue;
+            }
+            // NOTE: the files whose settings have not changed are not
+            // touched, the project file is usually under version control
+            if !file.outdated && group(&lines) == group(&file.lines) {
+                errors.extend(reasons.iter().map(|reason| reason.to_string()));
+                continue;
+            }
+
+            let path = if reasons.is_empty() {
+                file.path.clone()
+            } else {
+                conflict_path(&file.path)
+            };
+            if let Err(err) = write_settings(&path, &lines) {
+                errors.push(format!(
+                    "Could not save the configuration to {}: {}",
+                    path.display(),
+                    err
+                ));
+            } else if reasons.is_empty() {
+                file.lines = lines;
+                file.modified = modified(&file.path);
+                file.outdated = false;
+            } else {
+                errors.push(format!(
+                    "{} was not overwritten ({}). Its settings were saved to {}",
+                    file.path.display(),
+                    reasons.join(", "),
+                    path.display()
+                ));
+            }
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_merge() {
+        let base = lines("regexs = a\ncurrent_regex = 0\npty = false\nkey:j = down\nkey:k = up");
+        let ours = lines(
+            "regexs = a\nregexs = b\ncurrent_regex = 1\npty = false\nkey:j = down\nkey:k = up",
+        );
+        let theirs = lines(
+            "regexs = a\ncurrent_regex = 0\npty = true\nkey:j = down\nkey:n = down\nkey:k = up",
+        );
+        assert_eq!(
+            merge(&base, &ours, &theirs),
+            (
+                lines("regexs = a\nregexs = b\ncurrent_regex = 1\npty = true\nkey:j = down\nkey:n = down\nkey:k = up"),
+                vec![]
+            )
+        );
+
+        let theirs = lines("regexs = c\ncurrent_regex = 0\npty = false\nkey:j = down\nkey:k = up");
+        assert_eq!(
+            merge(&base, &ours, &theirs),
+            (ours.clone(), vec!["regexs".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_layer_settings() {
+        let input = "version = 11\nregexs = a\ncurrent_regex = 0\npty = true\n";
+        let path = Path::new(".cm.conf");
+        assert_eq!(
+            layer_settings(Layer::System, path, input).unwrap(),
+            (lines("regexs = a\npty = true"), vec![])
+        );
+        assert_eq!(
+            layer_settings(Layer::Project, path, input).unwrap(),
+            (lines("regexs = a"), vec!["pty".to_string()])
+        );
+        let (user, _) = layer_settings(Layer::User, path, input).unwrap();
+        assert!(user.contains(&"current_regex = 0".to_string()));
+        assert!(user.contains(&"rule = default".to_string()));
+        assert!(layer_settings(Layer::Project, path, "version = 19\nrule_cmd = x\n").is_err());
+    }
+
+    #[test]
+    fn test_project_layer() {
+        let dir = std::env::temp_dir().join(format!("cm-layers-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        let (user, project) = (
+            dir.join(CONFIG_FILE_NAME),
+            dir.join(PROJECT_CONFIG_FILE_NAME),
+        );
+        write_settings(&user, &lines("shell = /bin/sh\nregexs = a")).unwrap();
+        write_settings(
+            &project,
+            &lines("shell = /tmp/evil\nshell_args = -c\nkey:q = quit\nregexs = b"),
+        )
+        .unwrap();
+
+        let (_, profile, errors) = ConfigLayers::load(None, user, Some(project.clone()));
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(profile.shell, PathBuf::from("/bin/sh"));
+        assert_eq!(profile.regex_list.list.items, vec!["b".to_string()]);
+        assert_eq!(
+            errors,
+            vec![format!(
+                "{}: ignored shell, shell_args, keys of quit. A project file only sets regexs, cmds and rules",
+                project.display()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_layers() {
+        let file = |layer, text| ConfigFile {
+            layer,
+            path: PathBuf::new(),
+            lines: lines(text),
+            modified: None,
+            outdated: false,
+        };
+        let mut layers = ConfigLayers {
+            files: vec![
+                file(Layer::System, "shell = /bin/sh\npty = false"),
+                file(Layer::User, "pty = true\nregexs = a\ncurrent_regex = 0"),
+                file(Layer::Project, "regexs = b"),
+            ],
+            base: Vec::new(),
+            checked: Instant::now(),
+        };
+        layers.base = layers.compose();
+        assert_eq!(
+            layers.base,
+            lines("shell = /bin/sh\npty = true\nregexs = b\ncurrent_regex = 0")
+        );
+
+        let ours = lines("shell = /bin/bash\npty = true\nregexs = b\nregexs = c\ncurrent_regex = 1\nsession = true");
+        assert_eq!(
+            layers.split(&ours),
+            vec![
+                lines("shell = /bin/sh\npty = false"),
+                lines(
+                    "pty = true\nregexs = a\ncurrent_regex = 1\nshell = /bin/bash\nsession = true"
+                ),
+                lines("regexs = b\nregexs = c"),
+            ]
+        );
+    }
+}
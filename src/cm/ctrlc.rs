@@ -13,7 +13,7 @@ pub fn init() {
     if cfg!(unix) {
         unsafe {
             // TODO(#182): Explore portability issues of using signal(2)
-            libc::signal(libc::SIGINT, callback as libc::sighandler_t);
+            libc::signal(libc::SIGINT, callback as *const () as libc::sighandler_t);
         }
     }
 }
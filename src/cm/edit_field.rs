@@ -22,7 +22,7 @@ impl EditField {
             addstr(" ");
         }
         mv(y as i32, x as i32);
-        addstr(&self.buffer.get(begin..end).unwrap_or(""));
+        addstr(self.buffer.get(begin..end).unwrap_or(""));
         mv(y as i32, (x + self.cursor_x % w) as i32);
 
         cursor.x = (x + self.cursor_x) as i32;
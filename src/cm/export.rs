@@ -0,0 +1,187 @@
+use super::*;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+    /// `file:line:col: severity: message` understood by the default
+    /// `errorformat` of Vim (`vim -q`) and by Emacs' compilation-mode
+    Errorformat,
+    /// a minimal [SARIF](https://sarifweb.azurewebsites.net/) log: just
+    /// the results with their level, message and location
+    Json,
+}
+
+impl Format {
+    /// `.json` and `.sarif` files get the JSON, everything else gets the
+    /// errorformat
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") | Some("sarif") => Format::Json,
+            _ => Format::Errorformat,
+        }
+    }
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// The entries that can be jumped to: the ones with both the file and
+/// the line
+fn locatable(entries: &[QuickfixEntry]) -> impl Iterator<Item = &QuickfixEntry> {
+    entries
+        .iter()
+        .filter(|entry| entry.file.is_some() && entry.line.is_some())
+}
+
+fn sarif_result(entry: &QuickfixEntry) -> String {
+    let level = match entry.severity {
+        Some(Severity::Error) => "error",
+        Some(Severity::Warning) => "warning",
+        Some(Severity::Info) => "note",
+        None => "none",
+    };
+    let mut region = format!("\"startLine\":{}", entry.line.unwrap_or(1));
+    if let Some(col) = entry.col {
+        region.push_str(&format!(",\"startColumn\":{}", col));
+    }
+    format!(
+        "{{\"level\":\"{}\",\"message\":{{\"text\":{}}},\"locations\":[{{\"physicalLocation\":{{\"artifactLocation\":{{\"uri\":{}}},\"region\":{{{}}}}}}}]}}",
+        level,
+        json_string(&entry.message),
+        json_string(entry.file.as_deref().unwrap_or("")),
+        region
+    )
+}
+
+/// Writes the `entries` that have a location in the `format`. Returns
+/// the amount of the written entries.
+pub fn write<F: Write>(
+    stream: &mut F,
+    entries: &[QuickfixEntry],
+    format: Format,
+) -> io::Result<usize> {
+    match format {
+        Format::Errorformat => {
+            for entry in locatable(entries) {
+                writeln!(stream, "{}", entry.summary())?;
+            }
+        }
+        Format::Json => {
+            writeln!(
+                stream,
+                "{{\"version\":\"2.1.0\",\"runs\":[{{\"tool\":{{\"driver\":{{\"name\":\"cm\"}}}},\"results\":[{}]}}]}}",
+                locatable(entries)
+                    .map(sarif_result)
+                    .collect::<Vec<String>>()
+                    .join(",")
+            )?;
+        }
+    }
+    Ok(locatable(entries).count())
+}
+
+/// Writes the `entries` into the file at `path` in the format of its
+/// extension. See [Format::from_path](enum.Format.html#method.from_path).
+/// Nothing is written if there are entries but none of them has a
+/// location, since the regex most likely does not capture it.
+pub fn save(path: &Path, entries: &[QuickfixEntry]) -> Result<usize, String> {
+    if !entries.is_empty() && locatable(entries).next().is_none() {
+        return Err(format!(
+            "Could not export the matches to {}: none of the {} matches has a file and a line",
+            path.display(),
+            entries.len()
+        ));
+    }
+    File::create(path)
+        .and_then(|mut file| write(&mut file, entries, Format::from_path(path)))
+        .map_err(|err| {
+            format!(
+                "Could not export the matches to {}: {}",
+                path.display(),
+                err
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write() {
+        let entries = vec![
+            QuickfixEntry {
+                file: Some("src/\"a\".c".to_string()),
+                line: Some(3),
+                col: Some(10),
+                message: "expected ';'".to_string(),
+                severity: Some(Severity::Error),
+                index: 0,
+            },
+            QuickfixEntry {
+                file: Some("b.py".to_string()),
+                line: Some(7),
+                col: None,
+                message: "AssertionError".to_string(),
+                severity: None,
+                index: 4,
+            },
+            QuickfixEntry {
+                file: None,
+                line: None,
+                col: None,
+                message: "error: could not compile".to_string(),
+                severity: Some(Severity::Error),
+                index: 9,
+            },
+        ];
+
+        let mut output = Vec::new();
+        assert_eq!(
+            write(&mut output, &entries, Format::Errorformat).unwrap(),
+            2
+        );
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "src/\"a\".c:3:10: error: expected ';'\nb.py:7: AssertionError\n"
+        );
+
+        let mut output = Vec::new();
+        write(&mut output, &entries[1..], Format::Json).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"version\":\"2.1.0\",\"runs\":[{\"tool\":{\"driver\":{\"name\":\"cm\"}},\"results\":[\
+             {\"level\":\"none\",\"message\":{\"text\":\"AssertionError\"},\"locations\":[{\"physicalLocation\":\
+             {\"artifactLocation\":{\"uri\":\"b.py\"},\"region\":{\"startLine\":7}}}]}]}]}\n"
+        );
+
+        let path = std::env::temp_dir().join(format!("cm-export-{}.err", std::process::id()));
+        assert!(save(&path, &entries[2..]).is_err());
+        assert!(!path.exists());
+        assert_eq!(save(&path, &entries), Ok(2));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(Format::from_path(Path::new("cm.sarif")), Format::Json);
+        assert_eq!(
+            Format::from_path(Path::new("errors.err")),
+            Format::Errorformat
+        );
+    }
+}
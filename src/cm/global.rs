@@ -1,11 +1,16 @@
 use super::*;
 use pcre2::bytes::Regex;
+use std::path::PathBuf;
 
 #[derive(PartialEq, Clone, Copy)]
 pub enum Mode {
     Output,
     Regexs,
     Cmds,
+    History,
+    Rules,
+    /// the records of the current output list. See [Quickfix](struct.Quickfix.html)
+    Quickfix,
 }
 
 #[derive(PartialEq)]
@@ -13,6 +18,15 @@ pub enum BottomState {
     Nothing,
     Cmdline,
     Search,
+    /// the command line of a new tab is being entered
+    NewTab,
+    /// the new name of the current tab is being entered
+    TabName,
+    /// the name of a new rule made of the current regex and command is
+    /// being entered
+    RuleName,
+    /// the path to export the matches to is being entered
+    ExportPath,
 }
 
 pub struct Global {
@@ -25,14 +39,22 @@ pub struct Global {
     pub bottom_edit_field: BottomEditField,
     pub cursor: Cursor,
     /// user_provided_cmdline is the line provided by the user through the CLI of cm:
-    /// `cm <user_provided_cmdline>`
+    /// `cm <user_provided_cmdline>`. Afterwards it always reflects the
+    /// [Tab::user_provided_cmdline](struct.Tab.html#structfield.user_provided_cmdline)
+    /// of the current tab.
     pub user_provided_cmdline: Option<String>,
     pub search_regex: Option<Regex>,
-    pub shell: String,
+    /// Indicates that the next commands should be run in a
+    /// pseudo-terminal instead of a pipe. Initialized from
+    /// [Profile::pty](struct.Profile.html#structfield.pty)
+    pub pty: bool,
+    /// the file the matches were exported to last time. See
+    /// [export::save](export/fn.save.html)
+    pub export_path: Option<PathBuf>,
 }
 
 impl Global {
-    pub fn new(user_provided_cmdline: Option<String>) -> Self {
+    pub fn new(user_provided_cmdline: Option<String>, pty: bool) -> Self {
         Self {
             quit: false,
             mode: Mode::Output,
@@ -42,7 +64,8 @@ impl Global {
             cursor: Cursor::new(),
             user_provided_cmdline,
             search_regex: None,
-            shell: String::new(),
+            pty,
+            export_path: None,
         }
     }
 
@@ -67,6 +90,27 @@ impl Global {
                 self.mode = Mode::Cmds;
             }
             true
+        } else if key_map.is_bound(key_stroke, action::RULES_MODE) {
+            if self.mode == Mode::Rules {
+                self.mode = Mode::Output;
+            } else {
+                self.mode = Mode::Rules;
+            }
+            true
+        } else if key_map.is_bound(key_stroke, action::QUICKFIX_MODE) {
+            if self.mode == Mode::Quickfix {
+                self.mode = Mode::Output;
+            } else {
+                self.mode = Mode::Quickfix;
+            }
+            true
+        } else if key_map.is_bound(key_stroke, action::HISTORY_MODE) {
+            if self.mode == Mode::History {
+                self.mode = Mode::Output;
+            } else {
+                self.mode = Mode::History;
+            }
+            true
         } else if self.bottom_state == BottomState::Nothing
             && key_map.is_bound(key_stroke, action::START_SEARCH)
         {
@@ -81,11 +125,16 @@ impl Global {
             self.bottom_state = BottomState::Cmdline;
             self.bottom_edit_field.activate(
                 &mut self.cursor,
-                self.user_provided_cmdline
-                    .clone()
-                    .unwrap_or_else(String::new),
+                self.user_provided_cmdline.clone().unwrap_or_default(),
             );
             true
+        } else if self.bottom_state == BottomState::Nothing
+            && key_map.is_bound(key_stroke, action::NEW_TAB)
+        {
+            self.bottom_state = BottomState::NewTab;
+            self.bottom_edit_field
+                .activate(&mut self.cursor, String::new());
+            true
         } else {
             false
         }
@@ -1,6 +1,5 @@
 use super::*;
 use ncurses::*;
-use pcre2::bytes::Regex;
 use std::cmp::{max, min};
 
 pub struct ItemList<T: ToString + Clone> {
@@ -164,7 +163,7 @@ impl<T: ToString + Clone> ItemList<T> {
                         addstr(" ");
                     }
                     // addstr(&format!("{:?}", (left, right)));
-                    addstr(&line_to_render);
+                    addstr(line_to_render);
                     for _ in 0..right {
                         addstr(" ");
                     }
@@ -203,12 +202,4 @@ impl<T: ToString + Clone> ItemList<T> {
     pub fn is_at_end(&self) -> bool {
         self.cursor_y >= self.items.len() - 1
     }
-
-    pub fn is_current_line_matches(&mut self, regex: &Regex) -> bool {
-        if let Some(item) = self.current_item() {
-            regex.is_match(item.to_string().as_bytes()).unwrap()
-        } else {
-            false
-        }
-    }
 }
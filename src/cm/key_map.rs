@@ -3,7 +3,6 @@ use ncurses::*;
 use std::collections::BTreeSet;
 use std::io;
 use std::mem::{transmute, MaybeUninit};
-use std::string::ToString;
 
 // TODO(#152): KeyMap is not configuration right from the application
 pub struct KeyMap {
@@ -24,7 +23,12 @@ impl KeyMap {
                     *elem = MaybeUninit::new(Default::default());
                 }
 
-                unsafe { transmute::<_, [BTreeSet<KeyStroke>; action::LEN]>(key_map) }
+                unsafe {
+                    transmute::<
+                        [MaybeUninit<BTreeSet<KeyStroke>>; action::LEN],
+                        [BTreeSet<KeyStroke>; action::LEN],
+                    >(key_map)
+                }
             },
         }
     }
@@ -318,13 +322,125 @@ impl KeyMap {
             },
             action::CMDS_MODE,
         );
+        result.bind(
+            KeyStroke {
+                key: 'p' as i32,
+                alt: false,
+            },
+            action::TOGGLE_PTY,
+        );
+        result.bind(
+            KeyStroke {
+                key: 'o' as i32,
+                alt: false,
+            },
+            action::CYCLE_STREAM_FILTER,
+        );
+        result.bind(
+            KeyStroke {
+                key: 't' as i32,
+                alt: false,
+            },
+            action::NEW_TAB,
+        );
+        result.bind(
+            KeyStroke {
+                key: '\t' as i32,
+                alt: false,
+            },
+            action::NEXT_TAB,
+        );
+        result.bind(
+            KeyStroke {
+                key: KEY_BTAB,
+                alt: false,
+            },
+            action::PREV_TAB,
+        );
+        result.bind(
+            KeyStroke {
+                key: 'x' as i32,
+                alt: false,
+            },
+            action::CLOSE_TAB,
+        );
+        result.bind(
+            KeyStroke {
+                key: 'T' as i32,
+                alt: false,
+            },
+            action::RENAME_TAB,
+        );
+        result.bind(
+            KeyStroke {
+                key: 'f' as i32,
+                alt: false,
+            },
+            action::FORWARD,
+        );
+        result.bind(
+            KeyStroke {
+                key: 'H' as i32,
+                alt: false,
+            },
+            action::HISTORY_MODE,
+        );
+        result.bind(
+            KeyStroke {
+                key: 'R' as i32,
+                alt: false,
+            },
+            action::RULES_MODE,
+        );
+        result.bind(
+            KeyStroke {
+                key: 'A' as i32,
+                alt: false,
+            },
+            action::CYCLE_REGEX_SELECTION,
+        );
+        result.bind(
+            KeyStroke {
+                key: 'e' as i32,
+                alt: false,
+            },
+            action::NEXT_ERROR,
+        );
+        result.bind(
+            KeyStroke {
+                key: 'E' as i32,
+                alt: false,
+            },
+            action::PREV_ERROR,
+        );
+        result.bind(
+            KeyStroke {
+                key: 'Q' as i32,
+                alt: false,
+            },
+            action::QUICKFIX_MODE,
+        );
+        result.bind(
+            KeyStroke {
+                key: 'W' as i32,
+                alt: false,
+            },
+            action::EXPORT_MATCHES,
+        );
+        result.bind(
+            KeyStroke {
+                key: 'v' as i32,
+                alt: false,
+            },
+            action::TOGGLE_PREVIEW,
+        );
         result
     }
 
     pub fn to_file<F: io::Write>(&self, stream: &mut F) -> io::Result<()> {
         for (action_index, action_name) in action::NAMES.iter().enumerate() {
             for key in self.key_map[action_index].iter() {
-                writeln!(stream, "{} = {}", key.to_string(), action_name)?;
+                writeln!(stream, "{} = {}", key, action_name)?;
             }
         }
         Ok(())
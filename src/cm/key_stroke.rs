@@ -1,4 +1,5 @@
 use ncurses::*;
+use std::fmt;
 use std::str::FromStr;
 
 pub const KEY_ESCAPE: i32 = 0x1B;
@@ -204,7 +205,7 @@ pub fn key_of_name(name: &str) -> Result<i32, String> {
         .iter()
         .find(|(_, ncurses_name)| *ncurses_name == name)
     {
-        Ok(*key as i32)
+        Ok(*key)
     } else if let Some(stripped) = name.strip_prefix('#') {
         stripped.parse::<i32>().map_err(|e| e.to_string())
     } else {
@@ -212,10 +213,11 @@ pub fn key_of_name(name: &str) -> Result<i32, String> {
     }
 }
 
-impl ToString for KeyStroke {
-    fn to_string(&self) -> String {
+impl fmt::Display for KeyStroke {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         // TODO(#156): Human readable KeyStroke serialization format is required
-        format!(
+        write!(
+            f,
             "key:{}{}",
             name_of_key(self.key),
             if self.alt { ",alt" } else { "" }
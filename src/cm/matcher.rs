@@ -0,0 +1,510 @@
+use super::*;
+use pcre2::bytes::Regex;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// How the regex the output is matched with is chosen
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RegexSelection {
+    /// the current regex of the [Profile::regex_list](struct.Profile.html#structfield.regex_list)
+    Manual,
+    /// the regex that matches the most lines of the current output list
+    /// becomes the current one. See [best_regex](fn.best_regex.html)
+    Best,
+    /// every line is matched with the first regex of the
+    /// [Profile::regex_list](struct.Profile.html#structfield.regex_list)
+    /// that matches it
+    PerLine,
+}
+
+impl RegexSelection {
+    pub fn next(self) -> Self {
+        match self {
+            RegexSelection::Manual => RegexSelection::Best,
+            RegexSelection::Best => RegexSelection::PerLine,
+            RegexSelection::PerLine => RegexSelection::Manual,
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            RegexSelection::Manual => "the current regex",
+            RegexSelection::Best => "the regex that matches the most lines",
+            RegexSelection::PerLine => "the first regex that matches the line",
+        }
+    }
+}
+
+impl FromStr for RegexSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "manual" => Ok(RegexSelection::Manual),
+            "best" => Ok(RegexSelection::Best),
+            "per_line" => Ok(RegexSelection::PerLine),
+            _ => Err(format!("Unknown regex selection `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for RegexSelection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            RegexSelection::Manual => "manual",
+            RegexSelection::Best => "best",
+            RegexSelection::PerLine => "per_line",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The maximum amount of lines a multi-line record may span by default.
+/// See [is_multiline](fn.is_multiline.html)
+pub const DEFAULT_RECORD_WINDOW: usize = 8;
+
+/// The regexes that may match a newline (`\n`, `\R` or a literal one
+/// outside of a negated character class) are matched against a window
+/// of consecutive lines joined with `\n` instead of a single line, so a
+/// whole diagnostic like
+///
+/// ```text
+/// error[E0308]: mismatched types
+///  --> src/main.rs:4:18
+/// ```
+///
+/// becomes a single record.
+pub fn is_multiline(regex: &Regex) -> bool {
+    let chars: Vec<char> = regex.as_str().chars().collect();
+    // NOTE: `Some(true)` within a negated character class like `[^\n]`
+    let mut class: Option<bool> = None;
+    let mut quoted = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let negated = class == Some(true);
+        match (chars[i], chars.get(i + 1)) {
+            ('\\', Some('E')) if quoted => quoted = false,
+            ('\n', _) if !negated => return true,
+            _ if quoted => {
+                i += 1;
+                continue;
+            }
+            ('\\', Some('Q')) => quoted = true,
+            ('\\', Some('n')) | ('\\', Some('R')) if !negated => return true,
+            ('\\', _) => {}
+            ('[', Some(':')) if class.is_some() => {
+                // NOTE: `[:alpha:]` and friends
+                while i < chars.len() && !(chars[i] == ']' && chars[i - 1] == ':') {
+                    i += 1;
+                }
+            }
+            ('[', next) if class.is_none() => {
+                let negated = next == Some(&'^');
+                class = Some(negated);
+                i += 1 + negated as usize;
+                // NOTE: the `]` right after the `[` is a literal one
+                if chars.get(i) == Some(&']') {
+                    i += 1;
+                }
+                continue;
+            }
+            (']', _) if class.is_some() => class = None,
+            _ => {
+                i += 1;
+                continue;
+            }
+        }
+        i += if chars[i] == '\\' { 2 } else { 1 };
+    }
+    false
+}
+
+/// The consecutive lines of the output matched by a regex
+pub struct Record<'a> {
+    /// the lines of the record joined with `\n`
+    pub text: String,
+    /// the original bytes of the lines of the record joined with `\n`.
+    /// See [OutputLine::raw](struct.OutputLine.html#structfield.raw)
+    pub raw: Vec<u8>,
+    /// the amount of the lines the record spans
+    pub len: usize,
+    pub regex: &'a Regex,
+    /// the command to use for the record instead of the current one
+    pub cmd: Option<&'a str>,
+    pub severity: Option<Severity>,
+}
+
+/// A regex of the [Matcher](struct.Matcher.html) along with what its
+/// matches come with
+pub struct Pattern {
+    pub regex: Regex,
+    /// the command to use for the matches instead of the current one
+    pub cmd: Option<String>,
+    /// the severity of the matches that do not capture the
+    /// [SEVERITY_GROUP](constant.SEVERITY_GROUP.html) or capture something
+    /// unrecognizable there
+    pub severity: Option<Severity>,
+    /// the regex can match across the lines. See
+    /// [is_multiline](fn.is_multiline.html)
+    pub multiline: bool,
+}
+
+impl Pattern {
+    pub fn new(regex: Regex) -> Self {
+        Self {
+            multiline: is_multiline(&regex),
+            regex,
+            cmd: None,
+            severity: None,
+        }
+    }
+
+    fn severity_of(&self, text: &str) -> Option<Severity> {
+        let has_group = self
+            .regex
+            .capture_names()
+            .iter()
+            .any(|name| name.as_deref() == Some(SEVERITY_GROUP));
+        let captured = if has_group {
+            self.regex
+                .captures(text.as_bytes())
+                .ok()
+                .flatten()
+                .and_then(|caps| {
+                    caps.name(SEVERITY_GROUP)
+                        .map(|mat| (mat.start(), mat.end()))
+                })
+                .and_then(|(start, end)| Severity::classify(&text[start..end]))
+        } else {
+            None
+        };
+        captured.or(self.severity)
+    }
+}
+
+/// The regexes the lines of the output are matched with
+pub struct Matcher {
+    /// the regexes in the order of preference
+    patterns: Vec<Pattern>,
+    /// the maximum amount of lines of a multi-line record
+    window: usize,
+}
+
+impl Matcher {
+    pub fn single(regex: Regex) -> Self {
+        Self::new(vec![Pattern::new(regex)])
+    }
+
+    pub fn new(patterns: Vec<Pattern>) -> Self {
+        Self {
+            patterns,
+            window: DEFAULT_RECORD_WINDOW,
+        }
+    }
+
+    pub fn with_window(mut self, window: usize) -> Self {
+        self.window = window.max(1);
+        self
+    }
+
+    /// The amount of lines that have to be known to find a record
+    /// starting at a line
+    pub fn window(&self) -> usize {
+        if self.patterns.iter().any(|pattern| pattern.multiline) {
+            self.window
+        } else {
+            1
+        }
+    }
+
+    /// Finds the record of the first regex that matches starting at the
+    /// line at `index` of the `lines`. The regexes are matched against
+    /// the original bytes of the lines. PCRE2 refuses to match invalid
+    /// UTF-8 in the UTF mode, in which case the lossy text is used instead.
+    pub fn find_record<S: RawLine>(&self, lines: &[S], index: usize) -> Option<Record<'_>> {
+        let first = lines.get(index)?;
+        self.patterns.iter().find_map(|pattern| {
+            let regex = &pattern.regex;
+            let cmd = pattern.cmd.as_deref();
+            if !pattern.multiline {
+                let text = first.as_ref();
+                let raw = first.raw_bytes();
+                return match regex
+                    .is_match(raw)
+                    .or_else(|_| regex.is_match(text.as_bytes()))
+                {
+                    Ok(true) => Some(Record {
+                        text: text.to_string(),
+                        raw: raw.to_vec(),
+                        len: 1,
+                        regex,
+                        cmd,
+                        severity: pattern.severity_of(text),
+                    }),
+                    _ => None,
+                };
+            }
+
+            let window = &lines[index..lines.len().min(index + self.window)];
+            // NOTE: the lines of the output keep their `\n`, so they are
+            // trimmed to be joined with a single one
+            let join = |lines: &[S]| {
+                lines
+                    .iter()
+                    .map(|line| line.as_ref().trim_end_matches('\n'))
+                    .collect::<Vec<&str>>()
+                    .join("\n")
+            };
+            let join_raw = |lines: &[S]| {
+                lines
+                    .iter()
+                    .map(|line| {
+                        line.raw_bytes()
+                            .strip_suffix(b"\n")
+                            .unwrap_or(line.raw_bytes())
+                    })
+                    .collect::<Vec<&[u8]>>()
+                    .join(&b'\n')
+            };
+            let text = join(window);
+            let raw = join_raw(window);
+            // NOTE: the offsets of the match within the `raw` bytes are
+            // mapped onto the `text`
+            let mat = match regex.find(&raw) {
+                Ok(mat) => {
+                    mat.map(|mat| lossy_offset(&raw, mat.start())..lossy_offset(&raw, mat.end()))
+                }
+                Err(_) => regex
+                    .find(text.as_bytes())
+                    .ok()?
+                    .map(|mat| mat.start()..mat.end()),
+            }?;
+            // NOTE: the records that start on the next lines are found
+            // when the search starts from those lines
+            if mat.start > first.as_ref().trim_end_matches('\n').len() {
+                return None;
+            }
+            // NOTE: a match that ends with the `\n` does not span the
+            // next line
+            let len = text[..mat.end].trim_end_matches('\n').matches('\n').count() + 1;
+            let text = join(&window[..len]);
+            Some(Record {
+                severity: pattern.severity_of(&text),
+                text,
+                raw: join_raw(&window[..len]),
+                len,
+                regex,
+                cmd,
+            })
+        })
+    }
+
+    /// Identifies what the matcher matches, so the results computed with
+    /// one matcher can be reused with an equivalent one
+    pub fn fingerprint(&self) -> String {
+        let mut result = self.window().to_string();
+        for pattern in self.patterns.iter() {
+            result.push_str(&format!(
+                "\n{:?} {}",
+                pattern.severity,
+                pattern.regex.as_str()
+            ));
+        }
+        result
+    }
+
+    /// Counts the records of each severity starting at the `range` of
+    /// the `lines`
+    pub fn count_severities<S: RawLine>(&self, lines: &[S], range: Range<usize>) -> SeverityCounts {
+        let mut counts = SeverityCounts::default();
+        for index in range {
+            if let Some(record) = self.find_record(lines, index) {
+                counts.add(record.severity);
+            }
+        }
+        counts
+    }
+}
+
+/// The values of the groups of the `regex` matched against the `raw`
+/// bytes, `None` for the groups that did not participate in the match.
+/// If PCRE2 refuses the bytes the lossy text is matched instead, but the
+/// values are still taken from the bytes, so a file name like `caf\xe9.c`
+/// is not mangled.
+pub fn capture_groups(regex: &Regex, raw: &[u8]) -> Option<Vec<Option<Vec<u8>>>> {
+    let ranges: Vec<Option<Range<usize>>> = match regex.captures(raw) {
+        Ok(caps) => {
+            let caps = caps?;
+            (0..caps.len())
+                .map(|i| caps.get(i).map(|mat| mat.start()..mat.end()))
+                .collect()
+        }
+        Err(_) => {
+            let text = String::from_utf8_lossy(raw);
+            let caps = regex.captures(text.as_bytes()).ok()??;
+            (0..caps.len())
+                .map(|i| {
+                    caps.get(i)
+                        .map(|mat| raw_offset(raw, mat.start())..raw_offset(raw, mat.end()))
+                })
+                .collect()
+        }
+    };
+    Some(
+        ranges
+            .into_iter()
+            .map(|range| range.map(|range| raw[range].to_vec()))
+            .collect(),
+    )
+}
+
+/// The amount of the last lines of the output the regexes are scored on
+pub const SAMPLE_SIZE: usize = 1000;
+
+/// Returns the index of the regex that finds the most records in the
+/// last [SAMPLE_SIZE](constant.SAMPLE_SIZE.html) `lines`. The invalid
+/// regexes are `None` and never match anything. The `current` one wins
+/// the ties, so the selection does not jump around for no reason. `None`
+/// if none of the regexes match anything.
+pub fn best_regex(
+    regexes: &[Option<Regex>],
+    lines: &[OutputLine],
+    current: usize,
+    window: usize,
+) -> Option<usize> {
+    let sample = &lines[lines.len().saturating_sub(SAMPLE_SIZE)..];
+    let scores: Vec<usize> = regexes
+        .iter()
+        .map(|regex| match regex {
+            Some(regex) => {
+                let matcher = Matcher::single(regex.clone()).with_window(window);
+                (0..sample.len())
+                    .filter(|index| matcher.find_record(sample, *index).is_some())
+                    .count()
+            }
+            None => 0,
+        })
+        .collect();
+    let best = scores.iter().copied().max().filter(|score| *score > 0)?;
+    if scores.get(current) == Some(&best) {
+        Some(current)
+    } else {
+        scores.iter().position(|score| *score == best)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pcre2::bytes::RegexBuilder;
+
+    fn regex(pattern: &str) -> Regex {
+        RegexBuilder::new().utf(true).build(pattern).unwrap()
+    }
+
+    #[test]
+    fn test_best_regex() {
+        let lines: Vec<OutputLine> = [
+            "   Compiling cm v0.1.0",
+            "error[E0308]: mismatched types",
+            "  --> src/main.rs:4:5",
+            "  --> src/cm.rs:1:1",
+            "main.c:3:1: error: expected ';'",
+        ]
+        .iter()
+        .map(|line| OutputLine::new(line.to_string(), Vec::new(), None))
+        .collect();
+        let regexes = [
+            Some(regex(r"^(\S+\.c):(\d+):")),
+            Some(regex(r"--> (\S+):(\d+):(\d+)")),
+            None,
+        ];
+
+        assert_eq!(best_regex(&regexes, &lines, 0, 1), Some(1));
+        assert_eq!(best_regex(&regexes[..1], &lines, 0, 1), Some(0));
+        assert_eq!(best_regex(&regexes, &lines[..2], 1, 1), None);
+
+        let matcher = Matcher::new(
+            regexes
+                .iter()
+                .flatten()
+                .cloned()
+                .zip(vec![Some("cc".to_string()), None])
+                .map(|(regex, cmd)| Pattern {
+                    cmd,
+                    ..Pattern::new(regex)
+                })
+                .collect(),
+        );
+        let record = matcher.find_record(&lines, 4).unwrap();
+        assert_eq!(
+            (record.text.as_str(), record.cmd),
+            (lines[4].text.as_str(), Some("cc"))
+        );
+        assert_eq!(
+            matcher.find_record(&lines, 2).map(|record| record.cmd),
+            Some(None)
+        );
+        assert!(matcher.find_record(&lines, 0).is_none());
+        assert_eq!(matcher.window(), 1);
+    }
+
+    #[test]
+    fn test_multiline_record() {
+        let lines = [
+            "error[E0308]: mismatched types",
+            " --> src/main.rs:4:18",
+            "  |",
+            "warning: unused variable: `y`",
+            "",
+            "error: could not compile `cm`",
+        ];
+        let matcher = Matcher::single(regex(
+            r"^(?<severity>error|warning)(?:\[\w+\])?: .*\n(?:.*\n)*?\s*--> (?<file>[^:\s]+):(?<line>\d+)",
+        ))
+        .with_window(3);
+        assert_eq!(matcher.window(), 3);
+
+        let record = matcher.find_record(&lines, 0).unwrap();
+        assert_eq!(record.len, 2);
+        assert_eq!(record.severity, Some(Severity::Error));
+        assert_eq!(
+            record.text,
+            "error[E0308]: mismatched types\n --> src/main.rs:4:18"
+        );
+        assert!(matcher.find_record(&lines, 1).is_none());
+        // NOTE: the location is out of the window
+        assert!(matcher.find_record(&lines, 3).is_none());
+        assert!(matcher.find_record(&lines, 5).is_none());
+
+        let lines = ["error: oops\n", " --> src/main.rs:1:1\n"];
+        let record = matcher.find_record(&lines, 0).unwrap();
+        assert_eq!(record.text, "error: oops\n --> src/main.rs:1:1");
+
+        let lines = [
+            OutputLine::from("error: oops".to_string()),
+            OutputLine::from_bytes(b" --> caf\xe9.rs:1:1".to_vec(), Vec::new(), None),
+        ];
+        let record = matcher.find_record(&lines, 0).unwrap();
+        assert_eq!(record.text, "error: oops\n --> caf\u{FFFD}.rs:1:1");
+        assert_eq!(record.raw, b"error: oops\n --> caf\xe9.rs:1:1");
+        let groups = capture_groups(record.regex, &record.raw).unwrap();
+        assert_eq!(groups[2].as_deref(), Some(&b"caf\xe9.rs"[..]));
+    }
+
+    #[test]
+    fn test_is_multiline() {
+        let multiline = |pattern: &str| is_multiline(&regex(pattern));
+        assert!(multiline(r"error: .*\n\s*--> (\S+)"));
+        assert!(multiline(r"a\Rb"));
+        assert!(multiline("a[\\n]b"));
+        assert!(multiline("a\nb"));
+        assert!(!multiline(r"^([^\n:]+):(\d+)"));
+        assert!(!multiline(r"\\n(\d+)"));
+        assert!(!multiline(r"\Q\n\E(\d+)"));
+        assert!(!multiline(r"[^]\n](\d+)"));
+        assert!(multiline(r"[^[:space:]\n]\n"));
+        assert!(!multiline(r"[^[:space:]\n](\d+)"));
+    }
+}
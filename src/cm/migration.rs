@@ -1,6 +1,4 @@
 use super::*;
-use std::fs::{copy, read_to_string};
-use std::path::Path;
 use std::str::FromStr;
 
 pub type Type = fn(Vec<String>) -> Vec<String>;
@@ -22,30 +20,21 @@ fn migrate_v2_to_v3(lines: Vec<String>) -> Vec<String> {
     let mut shells = Vec::new();
     let mut current_shell = None;
 
+    // NOTE: the invalid lines are kept as they are, so parsing the
+    // migrated configuration reports them
     for line in lines.iter().map(|x| x.trim_start()) {
         if !line.is_empty() {
-            let (key, value) = config::split_key_value(line)
-                .unwrap_or_else(|| panic!("Invalid configuration line: {}", line));
-
-            match key {
-                "shells" => shells.push(value.to_string()),
-                "current_shell" => {
-                    current_shell = Some(
-                        value
-                            .parse::<usize>()
-                            .unwrap_or_else(|_| panic!("Not a number: {}", value)),
-                    )
-                }
+            match config::split_key_value(line) {
+                Some(("shells", value)) => shells.push(value.to_string()),
+                Some(("current_shell", value)) => current_shell = value.parse::<usize>().ok(),
                 _ => new_lines.push(line.to_string()),
             }
         }
     }
 
-    new_lines.push(format!(
-        "shell = {}",
-        shells[current_shell
-            .unwrap_or_else(|| panic!("Current shell was not provided by the config"))]
-    ));
+    if let Some(shell) = current_shell.and_then(|index| shells.get(index)) {
+        new_lines.push(format!("shell = {}", shell));
+    }
 
     new_lines
 }
@@ -61,66 +50,368 @@ fn migrate_v4_to_v5(lines: Vec<String>) -> Vec<String> {
 
     for line in lines.iter().map(|x| x.trim_start()) {
         if !line.is_empty() {
-            let (key, value) = config::split_key_value(line)
-                .unwrap_or_else(|| panic!("Invalid configuration line: {}", line));
-
-            if KeyStroke::from_str(key).is_ok() {
-                match value {
-                    "toggle_profile_panel" => {}
-                    "focus_forward" => {}
-                    "focus_backward" => {}
-                    _ => {
-                        new_lines.push(line.to_string());
+            match config::split_key_value(line) {
+                Some((key, "toggle_profile_panel"))
+                | Some((key, "focus_forward"))
+                | Some((key, "focus_backward"))
+                    if KeyStroke::from_str(key).is_ok() => {}
+                _ => new_lines.push(line.to_string()),
+            }
+        }
+    }
+
+    new_lines
+}
+
+fn migrate_v5_to_v6(mut lines: Vec<String>) -> Vec<String> {
+    lines.push("pty = false".to_string());
+    lines.push("key:p = toggle_pty".to_string());
+    lines
+}
+
+fn migrate_v6_to_v7(mut lines: Vec<String>) -> Vec<String> {
+    lines.push("key:o = cycle_stream_filter".to_string());
+    lines
+}
+
+fn migrate_v7_to_v8(mut lines: Vec<String>) -> Vec<String> {
+    lines.push("key:t = new_tab".to_string());
+    lines.push("key:HT = next_tab".to_string());
+    lines.push("key:BTAB = prev_tab".to_string());
+    lines.push("key:x = close_tab".to_string());
+    lines.push("key:T = rename_tab".to_string());
+    lines
+}
+
+fn migrate_v8_to_v9(mut lines: Vec<String>) -> Vec<String> {
+    lines.push("key:f = forward".to_string());
+    lines.push("key:H = history_mode".to_string());
+    lines
+}
+
+fn migrate_v9_to_v10(mut lines: Vec<String>) -> Vec<String> {
+    lines.push("session = false".to_string());
+    lines
+}
+
+fn migrate_v10_to_v11(mut lines: Vec<String>) -> Vec<String> {
+    lines.push("shell_args = -c".to_string());
+    lines
+}
+
+/// Turns the current regex and command into the first rule
+fn migrate_v11_to_v12(mut lines: Vec<String>) -> Vec<String> {
+    let mut regexs = Vec::new();
+    let mut cmds = Vec::new();
+    let mut current_regex = 0;
+    let mut current_cmd = 0;
+
+    for line in lines.iter().map(|x| x.trim_start()) {
+        if let Some((key, value)) = config::split_key_value(line) {
+            match key {
+                "regexs" => regexs.push(value.to_string()),
+                "cmds" => cmds.push(value.to_string()),
+                "current_regex" => current_regex = value.parse::<usize>().unwrap_or(0),
+                "current_cmd" => current_cmd = value.parse::<usize>().unwrap_or(0),
+                _ => {}
+            }
+        }
+    }
+
+    if let Some(regex) = regexs.get(current_regex).or_else(|| regexs.last()) {
+        lines.push("rule = default".to_string());
+        lines.push(format!("rule_regex = {}", regex));
+        if let Some(cmd) = cmds.get(current_cmd).or_else(|| cmds.last()) {
+            lines.push(format!("rule_cmd = {}", cmd));
+        }
+    }
+    lines.push("key:R = rules_mode".to_string());
+    lines
+}
+
+fn migrate_v12_to_v13(mut lines: Vec<String>) -> Vec<String> {
+    lines.push("regex_selection = manual".to_string());
+    lines.push("key:A = cycle_regex_selection".to_string());
+    lines
+}
+
+fn migrate_v13_to_v14(mut lines: Vec<String>) -> Vec<String> {
+    lines.push("record_window = 8".to_string());
+    lines
+}
+
+fn migrate_v14_to_v15(mut lines: Vec<String>) -> Vec<String> {
+    lines.push("key:e = next_error".to_string());
+    lines.push("key:E = prev_error".to_string());
+    lines
+}
+
+fn migrate_v15_to_v16(mut lines: Vec<String>) -> Vec<String> {
+    lines.push("key:Q = quickfix_mode".to_string());
+    lines
+}
+
+fn migrate_v16_to_v17(mut lines: Vec<String>) -> Vec<String> {
+    lines.push("key:W = export_matches".to_string());
+    lines
+}
+
+fn migrate_v17_to_v18(mut lines: Vec<String>) -> Vec<String> {
+    lines.push("key:v = toggle_preview".to_string());
+    lines
+}
+
+/// The length of the capture placeholder (`\1`, `{file}`, `{file|basename}`)
+/// at the start of `chars`, 0 if there is none. The raw ones (`\!1`,
+/// `{!file}`) are not counted, they are never quoted.
+fn placeholder_len(chars: &[char]) -> usize {
+    let is_name_char = |c: &char| c.is_ascii_alphanumeric() || *c == '_';
+    match chars {
+        ['\\', rest @ ..] => match rest.iter().take_while(|c| c.is_ascii_digit()).count() {
+            0 => 0,
+            digits => digits + 1,
+        },
+        ['{', rest @ ..] if rest.first().is_some_and(is_name_char) => {
+            match rest.iter().position(|c| *c == '}') {
+                Some(end) => end + 2,
+                None => 0,
+            }
+        }
+        _ => 0,
+    }
+}
+
+/// Since the captures are quoted for the shell, the quotes the template
+/// put around them end up in the command literally (`vim "\1"` runs
+/// `vim "'main.c'"`). The quotes around a lone capture are removed, the
+/// captures within the longer quoted strings are made raw (`\!1`,
+/// `{!file}`) so they are substituted as they were before.
+fn unquote_captures(template: &str) -> String {
+    let chars: Vec<char> = template.chars().collect();
+    let mut result = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if matches!(chars.get(i + 1), Some('\'') | Some('"') | Some('\\')) => {
+                result.extend(&chars[i..i + 2]);
+                i += 2;
+            }
+            '{' if i > 0 && chars[i - 1] == '$' => {
+                result.push('{');
+                i += 1;
+            }
+            quote @ '\'' | quote @ '"' => {
+                let mut end = i + 1;
+                while end < chars.len() && chars[end] != quote {
+                    if quote == '"' && chars[end] == '\\' && chars.get(end + 1) == Some(&'"') {
+                        end += 1;
+                    }
+                    end += 1;
+                }
+                let inner = &chars[i + 1..end.min(chars.len())];
+                if end < chars.len() && !inner.is_empty() && placeholder_len(inner) == inner.len() {
+                    result.extend(inner);
+                } else {
+                    result.push(quote);
+                    let mut j = 0;
+                    while j < inner.len() {
+                        let shell_parameter = j > 0 && inner[j - 1] == '$';
+                        match placeholder_len(&inner[j..]) {
+                            len if len == 0 || shell_parameter => {
+                                result.push(inner[j]);
+                                j += 1;
+                            }
+                            len => {
+                                result.push(inner[j]);
+                                result.push('!');
+                                result.extend(&inner[j + 1..j + len]);
+                                j += len;
+                            }
+                        }
+                    }
+                    if end < chars.len() {
+                        result.push(quote);
                     }
                 }
-            } else {
-                new_lines.push(line.to_string());
+                i = end + 1;
+            }
+            c => {
+                result.push(c);
+                i += 1;
             }
         }
     }
+    result
+}
 
-    new_lines
+/// See [unquote_captures](fn.unquote_captures.html)
+fn migrate_v18_to_v19(lines: Vec<String>) -> Vec<String> {
+    lines
+        .into_iter()
+        .map(|line| match config::split_key_value(line.trim_start()) {
+            Some((key @ "cmds", value)) | Some((key @ "rule_cmd", value))
+                if shell::parse_argv(value).is_none() =>
+            {
+                format!("{} = {}", key, unquote_captures(value))
+            }
+            _ => line,
+        })
+        .collect()
 }
 
-pub const CURRENT_VERSION: usize = 5;
+pub const CURRENT_VERSION: usize = 19;
 pub const MIGRATIONS: [Type; CURRENT_VERSION] = [
     migrate_v0_to_v1,
     migrate_v1_to_v2,
     migrate_v2_to_v3,
     migrate_v3_to_v4,
     migrate_v4_to_v5,
+    migrate_v5_to_v6,
+    migrate_v6_to_v7,
+    migrate_v7_to_v8,
+    migrate_v8_to_v9,
+    migrate_v9_to_v10,
+    migrate_v10_to_v11,
+    migrate_v11_to_v12,
+    migrate_v12_to_v13,
+    migrate_v13_to_v14,
+    migrate_v14_to_v15,
+    migrate_v15_to_v16,
+    migrate_v16_to_v17,
+    migrate_v17_to_v18,
+    migrate_v18_to_v19,
 ];
 
-pub fn read_and_migrate_file(filepath: &Path) -> Vec<String> {
-    let input = read_to_string(filepath).unwrap();
-    let mut lines = input.lines();
-    if let Some(version_line) = lines.next() {
-        match version_line
-            .split('=')
-            .map(|x| x.trim())
-            .collect::<Vec<&str>>()
-            .as_slice()
-        {
-            ["version", number] => {
-                let mut version_number = number.parse::<usize>().unwrap();
-                let mut input = lines.map(|x| x.to_string()).collect::<Vec<String>>();
-
-                if version_number < CURRENT_VERSION {
-                    copy(filepath, format!("{}.bak", filepath.display())).unwrap();
-                }
+/// The version of the configuration written in its first line
+pub fn version(input: &str) -> Result<usize, String> {
+    let version_line = input
+        .lines()
+        .next()
+        .ok_or_else(|| "Version line is not found".to_string())?;
+    match version_line
+        .split('=')
+        .map(|x| x.trim())
+        .collect::<Vec<&str>>()
+        .as_slice()
+    {
+        ["version", number] => number
+            .parse::<usize>()
+            .map_err(|_| "Version is not a number".to_string()),
+        _ => Err("Version line is not correct".to_string()),
+    }
+}
 
-                while version_number < CURRENT_VERSION {
-                    input = MIGRATIONS[version_number](input);
-                    version_number += 1;
-                }
+/// Migrates the configuration of any version to the
+/// [CURRENT_VERSION](constant.CURRENT_VERSION.html). Returns the lines
+/// without the version line.
+pub fn migrate(input: &str) -> Result<Vec<String>, String> {
+    migrate_with(input, true)
+}
 
-                return input;
-            }
-            _ => {
-                panic!("Version line is not correct");
-            }
+/// Migrates the configuration like [migrate](fn.migrate.html) but skips
+/// the migrations that only append the defaults of the new settings, so
+/// the result has only the settings that the file actually sets
+pub fn migrate_without_defaults(input: &str) -> Result<Vec<String>, String> {
+    migrate_with(input, false)
+}
+
+fn migrate_with(input: &str, defaults: bool) -> Result<Vec<String>, String> {
+    let mut version_number = version(input)?;
+    let mut lines = input
+        .lines()
+        .skip(1)
+        .map(|x| x.to_string())
+        .collect::<Vec<String>>();
+
+    while version_number < CURRENT_VERSION {
+        let migrated = MIGRATIONS[version_number](lines.clone());
+        if defaults || !migrated.starts_with(&lines) {
+            lines = migrated;
         }
+        version_number += 1;
     }
 
-    panic!("Version line is not found");
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_v11_to_v12() {
+        let lines = migrate_v11_to_v12(
+            [
+                "regexs = a",
+                "regexs = b",
+                "cmds = x",
+                "current_regex = 1",
+                "current_cmd = 5",
+            ]
+            .iter()
+            .map(|x| x.to_string())
+            .collect(),
+        );
+        assert_eq!(
+            &lines[5..],
+            &[
+                "rule = default",
+                "rule_regex = b",
+                "rule_cmd = x",
+                "key:R = rules_mode"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_migrate_without_defaults() {
+        let input =
+            "version = 2\nshells = /bin/sh\nshells = /bin/bash\ncurrent_shell = 1\nregexs = a\n";
+        assert_eq!(
+            migrate_without_defaults(input).unwrap(),
+            ["regexs = a", "shell = /bin/bash"]
+        );
+        let migrated = migrate(input).unwrap();
+        assert!(migrated.contains(&"rule = default".to_string()));
+        assert!(migrated.contains(&"pty = false".to_string()));
+    }
+
+    #[test]
+    fn test_unquote_captures() {
+        assert_eq!(unquote_captures(r#"vim "\1""#), r"vim \1");
+        assert_eq!(unquote_captures(r"vim +\2 '\1'"), r"vim +\2 \1");
+        assert_eq!(
+            unquote_captures(r#"code -g "{file|abspath}:{line}""#),
+            r#"code -g "{!file|abspath}:{!line}""#
+        );
+        assert_eq!(
+            unquote_captures(r#"echo "\"\1\" ${HOME}" '\!2'"#),
+            r#"echo "\"\!1\" ${HOME}" '\!2'"#
+        );
+        assert_eq!(
+            unquote_captures(r"awk '{ print $1 }' \1"),
+            r"awk '{ print $1 }' \1"
+        );
+        assert_eq!(unquote_captures(r#"vim \"\1"#), r#"vim \"\1"#);
+        assert_eq!(unquote_captures("vim \"\\1"), "vim \"\\!1");
+
+        let lines = migrate_v18_to_v19(
+            [
+                r#"cmds = vim "\1""#,
+                r#"rule_cmd = argv: vim "\1""#,
+                r#"regexs = "(\S+)""#,
+            ]
+            .iter()
+            .map(|x| x.to_string())
+            .collect(),
+        );
+        assert_eq!(
+            lines,
+            [
+                r"cmds = vim \1",
+                r#"rule_cmd = argv: vim "\1""#,
+                r#"regexs = "(\S+)""#
+            ]
+        );
+    }
 }
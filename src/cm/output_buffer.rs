@@ -1,40 +1,26 @@
 use super::*;
-use libc::*;
 use ncurses::*;
-use os_pipe::{pipe, PipeReader};
-use pcre2::bytes::{Match, Regex};
+use pcre2::bytes::Regex;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::os::unix::io::AsRawFd;
-use std::os::unix::process::CommandExt;
 use std::path::Path;
-use std::process::{Child, Command};
-
-// TODO(#94): mark_nonblocking does not work on Windows
-fn mark_nonblocking<Fd: AsRawFd>(fd: &mut Fd) {
-    unsafe {
-        let flags = libc::fcntl(fd.as_raw_fd(), F_GETFL, 0);
-        libc::fcntl(fd.as_raw_fd(), F_SETFL, flags | O_NONBLOCK);
-    }
-}
 
 struct CharMatch {
     start: usize,
     end: usize,
 }
 
-fn byte_match_to_char_match(mat: &Match, s: &str) -> Option<CharMatch> {
-    Some(CharMatch {
-        start: s.get(0..mat.start())?.chars().count(),
-        end: s.get(0..mat.end())?.chars().count(),
-    })
-}
-
 struct ByteMatch {
     start: usize,
     end: usize,
 }
 
+fn byte_match_to_char_match(mat: &ByteMatch, s: &str) -> Option<CharMatch> {
+    Some(CharMatch {
+        start: s.get(0..mat.start)?.chars().count(),
+        end: s.get(0..mat.end)?.chars().count(),
+    })
+}
+
 fn char_match_to_byte_match(mat: ByteMatch, s: &str) -> ByteMatch {
     ByteMatch {
         start: s.chars().take(mat.start).collect::<String>().len(),
@@ -42,26 +28,147 @@ fn char_match_to_byte_match(mat: ByteMatch, s: &str) -> ByteMatch {
     }
 }
 
-fn render_cmdline(line: &str, cmd: &str, regex: &Regex) -> Option<String> {
-    regex.captures_iter(line.as_bytes()).next().map(|cap_mat| {
-        let mut result = cmd.to_string();
-        if let Ok(caps) = cap_mat {
-            for i in 1..caps.len() {
-                if let Some(mat) = caps.get(i) {
-                    result = result.replace(
-                        format!("\\{}", i).as_str(),
-                        line.get(mat.start()..mat.end()).unwrap_or(""),
-                    )
+/// Renders the `byte_mat` part of the `line` with the `attr` on top of
+/// the line that was already rendered by [ItemList::render](struct.ItemList.html#method.render)
+/// into the `row` with the horizontal scroll of `scroll_x`.
+fn render_highlight(line: &str, byte_mat: ByteMatch, scroll_x: usize, row: Row, attr: attr_t) {
+    let Row { x, y, w } = row;
+    // TODO(#196): match highlighting does not respect the column width of the unicode characters
+    // NOTE: the match may come from the raw bytes of the line, whose
+    // offsets do not always land on the characters of the lossy text.
+    // Such a highlight is skipped rather than guessed.
+    let char_mat = match byte_match_to_char_match(&byte_mat, line) {
+        Some(char_mat) => char_mat,
+        None => return,
+    };
+    let char_start = usize::max(scroll_x, char_mat.start);
+    let char_end = usize::min(scroll_x + w, char_mat.end);
+    if char_start < char_end {
+        let effective_byte_mat = char_match_to_byte_match(
+            ByteMatch {
+                start: char_start,
+                end: char_end,
+            },
+            line,
+        );
+        mv(y as i32, (char_start - scroll_x + x) as i32);
+        attron(attr);
+        addstr(
+            line.get(effective_byte_mat.start..effective_byte_mat.end)
+                .unwrap_or("")
+                .trim_end_matches('\n'),
+        );
+        attroff(attr);
+    }
+}
+
+/// What the records of a [Matcher](struct.Matcher.html) highlight on a
+/// line of the output
+struct LineHighlight {
+    severity: Option<Severity>,
+    captures: Vec<ByteMatch>,
+}
+
+/// Finds the highlights of the records of the `matcher` on each one of
+/// the `h` `lines` starting at `top`. The records that start above `top`
+/// may still reach into the lines, so they are searched for as well.
+fn record_highlights(
+    matcher: &Matcher,
+    lines: &[OutputLine],
+    top: usize,
+    h: usize,
+) -> Vec<LineHighlight> {
+    let mut result: Vec<LineHighlight> = (0..h)
+        .map(|_| LineHighlight {
+            severity: None,
+            captures: Vec::new(),
+        })
+        .collect();
+    let first = top.saturating_sub(matcher.window() - 1);
+    for index in first..lines.len().min(top + h) {
+        let record = match matcher.find_record(lines, index) {
+            Some(record) => record,
+            None => continue,
+        };
+        for k in 0..record.len {
+            if (top..top + h).contains(&(index + k)) {
+                result[index + k - top].severity = record.severity;
+            }
+        }
+        // NOTE: we are ignoring any further potential
+        // capture matches (I don't like this term but
+        // that's what PCRE2 lib is calling it). For no
+        // particular reason. Just to simplify the
+        // implementation. Maybe in the future it will
+        // make sense.
+        // TODO(#189): regex capture highlighting is rendered with an offset
+        //   Probably due to pcre2 returning matches in bytes instead of chars
+        if let Ok(Some(caps)) = record.regex.captures(record.text.as_bytes()) {
+            let mut line_start = 0;
+            for (k, line) in record.text.split('\n').enumerate() {
+                let line_end = line_start + line.len();
+                if (top..top + h).contains(&(index + k)) {
+                    // NOTE: we are skiping first cap because it contains the
+                    // whole match which is not needed in our case
+                    for j in 1..caps.len() {
+                        if let Some(mat) = caps.get(j) {
+                            let start = mat.start().max(line_start);
+                            let end = mat.end().min(line_end);
+                            if start < end {
+                                result[index + k - top].captures.push(ByteMatch {
+                                    start: start - line_start,
+                                    end: end - line_start,
+                                });
+                            }
+                        }
+                    }
                 }
+                line_start = line_end + 1;
             }
         }
-        result
-    })
+    }
+    result
+}
+
+/// Whether the record of the `matcher` starting at the line at `index`
+/// reports an error. Used to jump between the errors skipping the
+/// warnings and the rest of the matches.
+fn is_error(matcher: &Matcher, lines: &[OutputLine], index: usize) -> bool {
+    matcher
+        .find_record(lines, index)
+        .is_some_and(|record| record.severity == Some(Severity::Error))
+}
+
+/// The file and the line the [Preview](struct.Preview.html) shows for the
+/// `record`. The location is extracted the same way the quickfix list
+/// does (see [QuickfixEntry::from_record](struct.QuickfixEntry.html#method.from_record)),
+/// the records without a line show the beginning of the file.
+fn preview_location(record: &Record) -> Option<(String, usize)> {
+    let entry = QuickfixEntry::from_record(record, 0);
+    Some((entry.file?, entry.line.unwrap_or(1)))
+}
+
+/// Substitutes the captures of the first match of the `regex` in the
+/// original bytes of the `line` into the `cmd` template. See
+/// [template::Template](template/struct.Template.html) for the syntax.
+/// Returns `None` if the `line` does not match and an error if the `cmd`
+/// is malformed.
+pub fn render_cmdline(
+    line: &[u8],
+    cmd: &str,
+    regex: &Regex,
+    shell: &shell::Shell,
+) -> Option<Result<String, String>> {
+    let groups = capture_groups(regex, line)?;
+    Some(template::Template::parse(cmd, regex).map(|template| template.render(&groups, shell)))
 }
 
 pub struct StatusLine {
     text_: String,
     error: bool,
+    /// rendered at the right edge of the status line as long as it does
+    /// not overlap with the text. See [SeverityCounts](struct.SeverityCounts.html)
+    summary: String,
 }
 
 impl StatusLine {
@@ -69,10 +176,11 @@ impl StatusLine {
         Self {
             text_: Default::default(),
             error: false,
+            summary: Default::default(),
         }
     }
 
-    pub fn render(&self, y: usize) {
+    pub fn render(&self, y: usize, w: usize) {
         let pair = if self.error {
             STATUS_ERROR_PAIR
         } else {
@@ -83,6 +191,16 @@ impl StatusLine {
         mv(y as i32, 0);
         addstr(&self.text_);
         attroff(COLOR_PAIR(pair));
+
+        let summary_width = self.summary.chars().count();
+        if !self.summary.is_empty() && self.text_.chars().count() + summary_width < w {
+            mv(y as i32, (w - summary_width) as i32);
+            addstr(&self.summary);
+        }
+    }
+
+    pub fn set_summary(&mut self, summary: String) {
+        self.summary = summary;
     }
 
     pub fn set_text(&mut self, text: String) {
@@ -102,75 +220,250 @@ impl StatusLine {
 }
 
 pub struct OutputBuffer {
-    pub lists: Vec<ItemList<String>>,
-    /// currently running process that generates data for OutputBuffer.
-    /// See [OutputBuffer::poll_cmdline_output](struct.OutputBuffer.html#method.poll_cmdline_output)
-    pub child: Option<(BufReader<PipeReader>, Child)>,
+    /// always contains at least one tab
+    pub tabs: Vec<Tab>,
+    pub current_tab: usize,
+    /// the size of the area the OutputBuffer was rendered into last time.
+    /// Used as the window size of the pseudo-terminals.
+    pub pane_size: (usize, usize),
     pub status_line: StatusLine,
+    /// the shell all of the command lines are run with
+    pub shell: shell::Shell,
+    pub quickfix: Quickfix,
+    pub preview: Preview,
+    /// The current tab has a running child and closing it awaits confirmation
+    close_requested: bool,
 }
 
 impl OutputBuffer {
-    pub fn new() -> Self {
+    pub fn new(shell: shell::Shell) -> Self {
         Self {
-            lists: Vec::new(),
-            child: None,
+            shell,
+            tabs: vec![Tab::new(None)],
+            current_tab: 0,
+            pane_size: (80, 24),
             status_line: StatusLine::new(),
+            quickfix: Quickfix::new(),
+            preview: Preview::new(),
+            close_requested: false,
         }
     }
 
+    pub fn tab(&self) -> &Tab {
+        &self.tabs[self.current_tab]
+    }
+
+    pub fn tab_mut(&mut self) -> &mut Tab {
+        &mut self.tabs[self.current_tab]
+    }
+
     pub fn push(&mut self, line: String) {
-        if let Some(list) = self.lists.last_mut() {
-            list.items.push(line);
+        self.tab_mut().push(line)
+    }
+
+    pub fn ctrlc(&mut self) {
+        self.tab_mut().ctrlc()
+    }
+
+    /// Kills the children of all of the tabs
+    pub fn kill_the_children(&mut self) {
+        for tab in self.tabs.iter_mut() {
+            tab.kill_the_child();
         }
     }
 
-    pub fn current_item(&self) -> Option<&String> {
-        self.lists.last().and_then(|x| x.current_item())
+    pub fn run_cmdline(&mut self, cmdline: String, pty: bool) {
+        let tab = &mut self.tabs[self.current_tab];
+        if let Err(err) = tab.run_cmdline(cmdline, &self.shell, pty, self.pane_size) {
+            self.status_line.set_error(err);
+        }
     }
 
-    pub fn jump_to_next_match(&mut self, regex: &Regex) {
-        if let Some(list) = self.lists.last_mut() {
-            list.down();
-            while !list.is_current_line_matches(regex) && !list.is_at_end() {
-                list.down();
-            }
+    /// Polls the children of all of the tabs, not only the current one.
+    /// Returns `true` if any of them received new input.
+    pub fn poll_cmdline_output(&mut self) -> bool {
+        let mut changed = false;
+        for tab in self.tabs.iter_mut() {
+            changed = tab.poll_cmdline_output() || changed;
         }
+        changed
     }
 
-    pub fn jump_to_prev_match(&mut self, regex: &Regex) {
-        if let Some(list) = self.lists.last_mut() {
-            list.up();
-            while !list.is_current_line_matches(regex) && !list.is_at_begin() {
-                list.up();
-            }
+    /// Replaces all of the tabs with the ones restored from a session.
+    /// See [session::load](session/fn.load.html)
+    pub fn restore(&mut self, tabs: Vec<Tab>, current_tab: usize) {
+        if !tabs.is_empty() {
+            self.kill_the_children();
+            self.tabs = tabs;
+            self.current_tab = current_tab;
         }
     }
 
-    pub fn ctrlc(&mut self) {
-        if cfg!(unix) {
-            if let Some((_, child)) = &self.child {
-                unsafe {
-                    // NOTE: taken from https://github.com/watchexec/watchexec/commit/f3c6df8845ed13e231cd9cbd88afdebe5fd97569
-                    // grep for @pgid
-                    libc::killpg(child.id() as i32, libc::SIGINT);
-                }
-            }
+    /// Opens a new tab right after the current one and switches to it
+    pub fn new_tab(&mut self, user_provided_cmdline: Option<String>) {
+        self.current_tab += 1;
+        self.tabs
+            .insert(self.current_tab, Tab::new(user_provided_cmdline));
+    }
+
+    /// Switches to an empty tab. A new one is opened unless the current
+    /// tab is still empty.
+    pub fn fresh_tab(&mut self) {
+        let tab = self.tab();
+        if !tab.lists.is_empty() || tab.child.is_some() || tab.input.is_some() {
+            self.new_tab(None);
+        }
+    }
+
+    /// Kills the child of the current tab and closes it. Closing the
+    /// last tab leaves a new empty one in its place.
+    pub fn close_tab(&mut self) {
+        self.tab_mut().kill_the_child();
+        self.tabs.remove(self.current_tab);
+        if self.tabs.is_empty() {
+            self.tabs.push(Tab::new(None));
+        }
+        if self.current_tab >= self.tabs.len() {
+            self.current_tab = self.tabs.len() - 1;
+        }
+    }
+
+    /// Closes the current tab unless its child is still running and the
+    /// closing was not `confirmed`. In that case asks to close the tab
+    /// once again to confirm killing the child.
+    fn close_tab_or_confirm(&mut self, confirmed: bool) {
+        if self.tab().child.is_some() && !confirmed {
+            self.close_requested = true;
+            self.status_line.set_error(format!(
+                "`{}` is still running. Close the tab again to kill it",
+                self.tab().title()
+            ));
+        } else {
+            self.close_tab();
+        }
+    }
+
+    /// Makes the [Global::user_provided_cmdline](struct.Global.html#structfield.user_provided_cmdline)
+    /// reflect the current tab after switching between the tabs.
+    pub fn sync_cmdline(&self, global: &mut Global) {
+        global.user_provided_cmdline = self.tab().user_provided_cmdline.clone();
+    }
+
+    pub fn next_tab(&mut self) {
+        self.current_tab = (self.current_tab + 1) % self.tabs.len();
+    }
+
+    pub fn prev_tab(&mut self) {
+        self.current_tab = (self.current_tab + self.tabs.len() - 1) % self.tabs.len();
+    }
+
+    /// Renders the titles of the tabs along with the status of their
+    /// children into a single row. Only rendered when there is more than
+    /// one tab.
+    fn render_tab_bar(&self, row: Row) {
+        let Row { x, y, w } = row;
+        mv(y as i32, x as i32);
+        let mut rendered = 0;
+        for (index, tab) in self.tabs.iter().enumerate() {
+            let title = format!(" {}: {} [{}] ", index + 1, tab.title(), tab.status());
+            let pair = if index == self.current_tab {
+                CURSOR_PAIR
+            } else {
+                UNFOCUSED_CURSOR_PAIR
+            };
+            attron(COLOR_PAIR(pair));
+            addstr(&title);
+            attroff(COLOR_PAIR(pair));
+            addstr(" ");
+            rendered += title.chars().count() + 1;
+        }
+        if rendered < w {
+            addstr(&" ".repeat(w - rendered));
         }
     }
 
+    /// The file and the line captured by the record at the cursor of the
+    /// current output list
+    fn current_location(&self, matcher: &Matcher) -> Option<(String, usize)> {
+        let list = &self.tab().current_list()?.list;
+        let record = matcher.find_record(&list.items, list.cursor_y)?;
+        preview_location(&record)
+    }
+
     pub fn render(
         &mut self,
         rect: Rect,
         focused: bool,
-        regex_result: Option<Result<Regex, pcre2::Error>>,
+        matcher_result: Option<Result<Matcher, pcre2::Error>>,
     ) {
-        if let Some(list) = self.lists.last_mut() {
+        let rect = if self.preview.visible {
+            let (output_rect, preview_rect) = rect.vertical_split(2);
+            let location = match &matcher_result {
+                Some(Ok(matcher)) => self.current_location(matcher),
+                _ => None,
+            };
+            self.preview.render(
+                preview_rect,
+                location.as_ref().map(|(file, line)| (file.as_str(), *line)),
+            );
+            output_rect
+        } else {
+            rect
+        };
+
+        let rect = if self.tabs.len() > 1 && rect.h > 0 {
+            self.render_tab_bar(Row {
+                x: rect.x,
+                y: rect.y,
+                w: rect.w,
+            });
+            Rect {
+                y: rect.y + 1,
+                h: rect.h - 1,
+                ..rect
+            }
+        } else {
+            rect
+        };
+
+        // NOTE: KEY_RESIZE always causes a rerender (See NOTE(rerender)),
+        // so this is where the pseudo-terminal of the child learns about
+        // the new size of the terminal.
+        if self.pane_size != (rect.w, rect.h) {
+            self.pane_size = (rect.w, rect.h);
+            for tab in self.tabs.iter() {
+                for master in tab.pty_masters.iter() {
+                    let _ = pty::set_window_size(master, rect.w, rect.h);
+                }
+            }
+        }
+
+        let current_tab = self.current_tab;
+        let summary = match (self.tabs[current_tab].current_list_mut(), &matcher_result) {
+            (Some(output_list), Some(Ok(matcher))) => {
+                output_list.severity_counts(matcher).to_string()
+            }
+            _ => String::new(),
+        };
+        self.status_line.set_summary(summary);
+
+        if let Some(OutputList { list, .. }) = self.tabs[current_tab].current_list_mut() {
             list.render(rect, focused);
 
             let Rect { x, y, w, h } = rect;
             if h > 0 {
+                let highlights = match &matcher_result {
+                    Some(Ok(matcher)) => record_highlights(matcher, &list.items, list.scroll_y, h),
+                    _ => (0..h)
+                        .map(|_| LineHighlight {
+                            severity: None,
+                            captures: Vec::new(),
+                        })
+                        .collect(),
+                };
+
                 // TODO(#16): word wrapping for long lines
-                for i in 0..h {
+                for (i, highlight) in highlights.iter().enumerate() {
                     if list.scroll_y + i < list.items.len() {
                         let item = &list.items[list.scroll_y + i];
                         let selected = list.scroll_y + i == list.cursor_y;
@@ -184,121 +477,162 @@ impl OutputBuffer {
                             MATCH_PAIR
                         };
 
-                        if let Some(Ok(regex)) = &regex_result {
-                            // NOTE: we are ignoring any further potential
-                            // capture matches (I don't like this term but
-                            // that's what PCRE2 lib is calling it). For no
-                            // particular reason. Just to simplify the
-                            // implementation. Maybe in the future it will
-                            // make sense.
-                            // TODO(#189): regex capture highlighting is rendered with an offset
-                            //   Probably due to pcre2 returning matches in bytes instead of chars
-                            let cap_mats = regex.captures_iter(item.as_bytes()).next();
-                            if let Some(cap_mat) = cap_mats {
-                                if let Ok(caps) = cap_mat {
-                                    // NOTE: we are skiping first cap because it contains the
-                                    // whole match which is not needed in our case
-                                    // TODO(#196): match highlighting does not respect the column width of the unicode characters
-                                    for j in 1..caps.len() {
-                                        if let Some(byte_mat) = caps.get(j) {
-                                            // TODO(#197): test cm on incorrect utf-8 data
-                                            let char_mat =
-                                                byte_match_to_char_match(&byte_mat, item).unwrap();
-                                            let char_start =
-                                                usize::max(list.scroll_x, char_mat.start);
-                                            let char_end =
-                                                usize::min(list.scroll_x + w, char_mat.end);
-                                            if char_start != char_end {
-                                                let effective_byte_mat = char_match_to_byte_match(
-                                                    ByteMatch {
-                                                        start: char_start,
-                                                        end: char_end,
-                                                    },
-                                                    item,
-                                                );
-                                                mv(
-                                                    (y + i) as i32,
-                                                    (char_start - list.scroll_x + x) as i32,
-                                                );
-                                                attron(COLOR_PAIR(cap_pair));
-                                                addstr(
-                                                    item.get(
-                                                        effective_byte_mat.start
-                                                            ..effective_byte_mat.end,
-                                                    )
-                                                    .unwrap_or(""),
-                                                );
-                                                attroff(COLOR_PAIR(cap_pair));
-                                            }
-                                        }
-                                    }
-                                }
+                        if !selected {
+                            let line_pair = match highlight.severity {
+                                Some(severity) => Some(severity.pair()),
+                                None if item.stream == Some(Stream::Stderr) => Some(STDERR_PAIR),
+                                None => None,
+                            };
+                            if let Some(line_pair) = line_pair {
+                                render_highlight(
+                                    &item.text,
+                                    ByteMatch {
+                                        start: 0,
+                                        end: item.text.len(),
+                                    },
+                                    list.scroll_x,
+                                    Row { x, y: y + i, w },
+                                    COLOR_PAIR(line_pair),
+                                );
+                            }
+
+                            for span in item.spans.iter() {
+                                render_highlight(
+                                    &item.text,
+                                    ByteMatch {
+                                        start: span.start,
+                                        end: span.end,
+                                    },
+                                    list.scroll_x,
+                                    Row { x, y: y + i, w },
+                                    ansi_attr(span.style),
+                                );
                             }
                         }
+
+                        for byte_mat in highlight.captures.iter() {
+                            render_highlight(
+                                &item.text,
+                                ByteMatch {
+                                    start: byte_mat.start,
+                                    end: byte_mat.end,
+                                },
+                                list.scroll_x,
+                                Row { x, y: y + i, w },
+                                COLOR_PAIR(cap_pair),
+                            );
+                        }
                     }
                 }
             }
         }
     }
 
-    pub fn kill_the_child(&mut self) {
-        if let Some((_, child)) = &mut self.child {
-            unsafe {
-                // NOTE: taken from https://github.com/watchexec/watchexec/commit/f3c6df8845ed13e231cd9cbd88afdebe5fd97569
-                // grep for @pgid
-                libc::killpg(child.id() as i32, libc::SIGTERM);
+    pub fn render_history(&mut self, rect: Rect, focused: bool) {
+        let tab = self.tab_mut();
+        tab.refresh_history();
+        tab.history.render(rect, focused);
+    }
+
+    pub fn handle_history_key(
+        &mut self,
+        key_stroke: KeyStroke,
+        profile: &Profile,
+        global: &mut Global,
+    ) {
+        let key_map = &profile.key_map;
+        if !global.handle_key(key_stroke, key_map) {
+            let tab = self.tab_mut();
+            if key_map.is_bound(key_stroke, action::ACCEPT) {
+                tab.open(tab.history.cursor_y);
+                self.refresh_status_line(profile);
+            } else if key_map.is_bound(key_stroke, action::DELETE) {
+                if let Err(err) = tab.prune(tab.history.cursor_y) {
+                    self.status_line.set_error(err);
+                } else {
+                    self.refresh_status_line(profile);
+                }
+            } else if key_map.is_bound(key_stroke, action::BACK) {
+                global.mode = Mode::Output;
+            } else {
+                tab.history.handle_key(key_stroke, key_map);
             }
-            child
-                .wait()
-                .expect("Error waiting for currently running child process");
-            self.child = None;
         }
     }
 
-    pub fn run_cmdline(&mut self, cmdline: String, shell: &Path) {
-        // TODO(#102): cm does not warn the user when it kills the child process
-        self.kill_the_child();
-
-        // @ref(#40) customize the argument of Command::new()
-        let mut command = Command::new(shell);
-        command.arg("-c");
-        command.arg(cmdline.clone());
-        let (mut reader, writer) =
-            pipe().expect("Could not create a pipe for collecting output from a child process");
-        let writer_clone = writer
-            .try_clone()
-            .expect("Could not clone the pipe for collecting output from a child process");
-        command.stdout(writer);
-        command.stderr(writer_clone);
-        // NOTE: taken from https://github.com/watchexec/watchexec/commit/f3c6df8845ed13e231cd9cbd88afdebe5fd97569
-        // grep for @pgid
-        unsafe {
-            command.pre_exec(|| {
-                libc::setpgid(0, 0);
-                Ok(())
-            });
-        }
-        // @ref(#40) this part should fail if the user provided
-        // non-existing shell. So should probably do not unwrap it and
-        // properly report the fail somehow without crashing the app.
-        let child = command.spawn().expect("Could not spawn a child process");
-        drop(command);
+    /// Exports the records of the `matcher` in the current output list to
+    /// the file at `path`. Returns the amount of the exported records.
+    pub fn export(&self, path: &Path, matcher: &Matcher) -> Result<usize, String> {
+        let lines = self
+            .tab()
+            .current_list()
+            .map(|output_list| output_list.list.items.as_slice())
+            .unwrap_or(&[]);
+        export::save(path, &collect_entries(matcher, lines))
+    }
 
-        let mut new_list = ItemList::new();
-        new_list.items.push(format!(
-            "PID: {}, Command: {}",
-            child.id(),
-            cmdline.as_str()
-        ));
-        self.lists.push(new_list);
+    pub fn render_quickfix(
+        &mut self,
+        rect: Rect,
+        focused: bool,
+        matcher_result: Option<Result<Matcher, pcre2::Error>>,
+    ) {
+        let output_list = self.tabs[self.current_tab].current_list_mut();
+        let matcher = match &matcher_result {
+            Some(Ok(matcher)) => Some(matcher),
+            _ => None,
+        };
+        self.quickfix.refresh(matcher, output_list);
+        self.quickfix.list.render(rect, focused);
+    }
 
-        mark_nonblocking(&mut reader);
-        let output = BufReader::new(reader);
+    /// Moves the cursor of the current output list to the record of the
+    /// current entry of the [Quickfix](struct.Quickfix.html)
+    fn sync_quickfix_cursor(&mut self) {
+        if let Some(index) = self.quickfix.current().map(|entry| entry.index) {
+            let current_tab = self.current_tab;
+            if let Some(output_list) = self.tabs[current_tab].current_list_mut() {
+                if index < output_list.list.items.len() {
+                    output_list.list.cursor_y = index;
+                }
+            }
+        }
+    }
 
-        self.child = Some((output, child));
+    pub fn handle_quickfix_key(
+        &mut self,
+        key_stroke: KeyStroke,
+        profile: &Profile,
+        global: &mut Global,
+    ) {
+        let key_map = &profile.key_map;
+        if !global.handle_key(key_stroke, key_map) {
+            if key_map.is_bound(key_stroke, action::ACCEPT)
+                || key_map.is_bound(key_stroke, action::RUN)
+            {
+                self.sync_quickfix_cursor();
+                let cmdline_result = match &profile.matcher() {
+                    Some(Ok(matcher)) => self.render_current_cmdline(matcher, profile),
+                    _ => None,
+                };
+                match cmdline_result {
+                    Some(Ok(cmdline)) => self.fork_cmdline(cmdline),
+                    Some(Err(err)) => self.status_line.set_error(err),
+                    None => {}
+                }
+            } else if key_map.is_bound(key_stroke, action::TOGGLE_PREVIEW) {
+                self.preview.visible = !self.preview.visible;
+            } else if key_map.is_bound(key_stroke, action::BACK) {
+                global.mode = Mode::Output;
+            } else {
+                self.quickfix.list.handle_key(key_stroke, key_map);
+                self.sync_quickfix_cursor();
+                self.refresh_status_line(profile);
+            }
+        }
     }
 
-    pub fn fork_cmdline(&mut self, cmdline: String, shell: &Path) {
+    pub fn fork_cmdline(&mut self, cmdline: String) {
         // NOTE: It is important to call endwin() function before running any child processes.
         // According to https://invisible-island.net/ncurses/man/curs_initscr.3x.html#h3-endwin
         //
@@ -309,19 +643,25 @@ impl OutputBuffer {
         //
         // Add we will call refresh(3x) after the child process exists on the next iteration of the event loop
         endwin();
-        // TODO(#40): shell is not customizable
-        //   Grep for @ref(#40)
-
-        let exit = Command::new(shell)
-            .stdin(
-                File::open("/dev/tty").expect("Could not open /dev/tty as stdin for child process"),
-            )
-            .arg("-c")
-            .arg(cmdline.clone())
-            .spawn()
-            .expect("Could not spawn child process")
-            .wait()
-            .expect("Error waiting for output of child process");
+
+        let spawned = self.shell.command(&cmdline).and_then(|mut command| {
+            command
+                .stdin(
+                    File::open("/dev/tty")
+                        .expect("Could not open /dev/tty as stdin for child process"),
+                )
+                .spawn()
+                .map_err(|err| format!("Could not run `{}`: {}", cmdline, err))
+        });
+        let exit = match spawned {
+            Ok(mut child) => child
+                .wait()
+                .expect("Error waiting for output of child process"),
+            Err(err) => {
+                self.status_line.set_error(err);
+                return;
+            }
+        };
 
         if !exit.success() {
             match exit.code() {
@@ -335,170 +675,293 @@ impl OutputBuffer {
         }
     }
 
-    /// Polls changes from the currently running child (see
-    /// [OutputBuffer::run_cmdline](struct.OutputBuffer.html#method.run_cmdline),
-    /// [OutputBuffer::child](struct.OutputBuffer.html#structfield.child)).
-    ///
-    /// Returns `true` if new input was received, `false` when nothing
-    /// was received.
-    pub fn poll_cmdline_output(&mut self) -> bool {
-        let mut changed = false;
-
-        if let Some((reader, child)) = &mut self.child {
-            let mut line = String::new();
-            const FLUSH_BUFFER_LIMIT: usize = 1024;
-            for _ in 0..FLUSH_BUFFER_LIMIT {
-                line.clear();
-                match reader.read_line(&mut line) {
-                    Ok(0) => break,
-                    Ok(_) => {
-                        if let Some(list) = self.lists.last_mut() {
-                            // TODO(#185): move the tab expansion to ItemList so it's available for every list-like component
-                            list.items.push(expand_tabs(&line, TABSIZE() as usize));
-                            changed = true;
-                        }
-                    }
-                    _ => break,
-                }
-            }
-
-            if let Some(status) = child
-                .try_wait()
-                .expect("Error attempting to wait for child output")
-            {
-                match status.code() {
-                    Some(code) => {
-                        if let Some(list) = self.lists.last_mut() {
-                            list.items.push(format!(
-                                "-- Execution Finished with status code: {} --",
-                                code
-                            ));
-                            changed = true;
-                        }
-                    }
-                    None => {
-                        if let Some(list) = self.lists.last_mut() {
-                            list.items
-                                .push("-- Execution Terminated by a signal --".to_string());
-                            changed = true;
-                        }
-                    }
-                }
-                self.child = None
-            }
-        }
-
-        changed
+    /// Renders the command for the record of the `matcher` that starts
+    /// at the current item
+    fn render_current_cmdline(
+        &self,
+        matcher: &Matcher,
+        profile: &Profile,
+    ) -> Option<Result<String, String>> {
+        let list = &self.tab().current_list()?.list;
+        let record = matcher.find_record(&list.items, list.cursor_y)?;
+        let cmd = record
+            .cmd
+            .map(String::from)
+            .or_else(|| profile.current_cmd())?;
+        render_cmdline(&record.raw, &cmd, record.regex, &self.shell)
     }
 
     pub fn refresh_status_line(&mut self, profile: &Profile) {
-        match (
-            &profile.current_regex(),
-            &profile.current_cmd(),
-            &self.current_item(),
-        ) {
-            (Some(Ok(regex)), Some(cmd), Some(line)) => {
-                let cmdline = render_cmdline(line, &cmd, regex).unwrap_or_default();
-                self.status_line.set_text(cmdline);
-            }
-            (Some(Err(err)), _, _) => self.status_line.set_error(err.to_string()),
-            _ => self.status_line.clear(),
+        match &profile.matcher() {
+            Some(Ok(matcher)) => match self.render_current_cmdline(matcher, profile) {
+                Some(Ok(cmdline)) => self.status_line.set_text(cmdline),
+                Some(Err(err)) => self.status_line.set_error(err),
+                None => self.status_line.clear(),
+            },
+            Some(Err(err)) => self.status_line.set_error(err.to_string()),
+            None => self.status_line.clear(),
         };
     }
 
     pub fn handle_key(
         &mut self,
         key_stroke: KeyStroke,
-        profile: &Profile,
+        profile: &mut Profile,
         global: &mut Global,
-        shell: &Path,
     ) {
         let key_map = &profile.key_map;
-        let regex_result = profile.current_regex();
+        let matcher_result = profile.matcher();
+        // NOTE: any other key cancels the closing of the tab
+        let close_requested = std::mem::take(&mut self.close_requested);
 
-        let cmdline_result = match (&regex_result, &profile.current_cmd(), &self.current_item()) {
-            (Some(Ok(regex)), Some(cmd), Some(line)) => render_cmdline(line, &cmd, regex),
+        let cmdline_result = match &matcher_result {
+            Some(Ok(matcher)) => self.render_current_cmdline(matcher, profile),
             _ => None,
         };
 
         if !global.handle_key(key_stroke, key_map) {
             if key_map.is_bound(key_stroke, action::RUN_INTO_ITSELF) {
-                if let Some(cmdline) = &cmdline_result {
-                    self.run_cmdline(cmdline.clone(), shell);
-                    self.refresh_status_line(profile);
+                match &cmdline_result {
+                    Some(Ok(cmdline)) => {
+                        self.run_cmdline(cmdline.clone(), global.pty);
+                        self.refresh_status_line(profile);
+                    }
+                    Some(Err(err)) => self.status_line.set_error(err.clone()),
+                    None => {}
                 }
             } else if key_map.is_bound(key_stroke, action::RUN) {
-                if let Some(cmdline) = &cmdline_result {
-                    self.fork_cmdline(cmdline.clone(), &profile.shell);
+                match &cmdline_result {
+                    Some(Ok(cmdline)) => self.fork_cmdline(cmdline.clone()),
+                    Some(Err(err)) => self.status_line.set_error(err.clone()),
+                    None => {}
                 }
             } else if key_map.is_bound(key_stroke, action::BACK) {
-                self.lists.pop();
+                self.tab_mut().back();
+                self.refresh_status_line(profile);
+            } else if key_map.is_bound(key_stroke, action::FORWARD) {
+                self.tab_mut().forward();
                 self.refresh_status_line(profile);
             } else if key_map.is_bound(key_stroke, action::RERUN) {
-                if let Some(cmdline) = global.user_provided_cmdline.clone() {
-                    self.run_cmdline(cmdline, shell);
+                if let Some(cmdline) = self.tab().user_provided_cmdline.clone() {
+                    self.run_cmdline(cmdline, global.pty);
                     self.refresh_status_line(profile);
                 }
+            } else if key_map.is_bound(key_stroke, action::TOGGLE_PTY) {
+                global.pty = !global.pty;
+                self.status_line.set_text(format!(
+                    "Next commands will run in a {}",
+                    if global.pty {
+                        "pseudo-terminal"
+                    } else {
+                        "pipe"
+                    }
+                ));
+            } else if key_map.is_bound(key_stroke, action::TOGGLE_PREVIEW) {
+                self.preview.visible = !self.preview.visible;
+            } else if key_map.is_bound(key_stroke, action::CYCLE_STREAM_FILTER) {
+                let current_tab = self.current_tab;
+                if let Some(list) = self.tabs[current_tab].current_list_mut() {
+                    list.set_filter(list.filter.next());
+                    self.status_line
+                        .set_text(format!("Showing {}", list.filter.description()));
+                }
+            } else if key_map.is_bound(key_stroke, action::CYCLE_REGEX_SELECTION) {
+                profile.regex_selection = profile.regex_selection.next();
+                self.status_line.set_text(format!(
+                    "Matching the output with {}",
+                    profile.regex_selection.description()
+                ));
             } else if key_map.is_bound(key_stroke, action::PREV_MATCH) {
-                if let Some(Ok(regex)) = &regex_result {
-                    self.jump_to_prev_match(&regex);
+                if let Some(Ok(matcher)) = &matcher_result {
+                    self.tab_mut().jump_to_prev_match(|lines, index| {
+                        matcher.find_record(lines, index).is_some()
+                    });
                     self.refresh_status_line(profile);
                 }
             } else if key_map.is_bound(key_stroke, action::NEXT_MATCH) {
-                if let Some(Ok(regex)) = &regex_result {
-                    self.jump_to_next_match(&regex);
+                if let Some(Ok(matcher)) = &matcher_result {
+                    self.tab_mut().jump_to_next_match(|lines, index| {
+                        matcher.find_record(lines, index).is_some()
+                    });
+                    self.refresh_status_line(profile);
+                }
+            } else if key_map.is_bound(key_stroke, action::PREV_ERROR) {
+                if let Some(Ok(matcher)) = &matcher_result {
+                    self.tab_mut()
+                        .jump_to_prev_match(|lines, index| is_error(matcher, lines, index));
+                    self.refresh_status_line(profile);
+                }
+            } else if key_map.is_bound(key_stroke, action::NEXT_ERROR) {
+                if let Some(Ok(matcher)) = &matcher_result {
+                    self.tab_mut()
+                        .jump_to_next_match(|lines, index| is_error(matcher, lines, index));
                     self.refresh_status_line(profile);
                 }
             } else if key_map.is_bound(key_stroke, action::NEXT_SEARCH_MATCH) {
                 if let Some(regex) = &global.search_regex {
-                    self.jump_to_next_match(&regex);
+                    self.tab_mut()
+                        .jump_to_next_match(|lines, index| lines[index].is_match(regex));
                     self.refresh_status_line(profile);
                 }
             } else if key_map.is_bound(key_stroke, action::PREV_SEARCH_MATCH) {
                 if let Some(regex) = &global.search_regex {
-                    self.jump_to_prev_match(&regex);
+                    self.tab_mut()
+                        .jump_to_prev_match(|lines, index| lines[index].is_match(regex));
+                    self.refresh_status_line(profile);
+                }
+            } else if key_map.is_bound(key_stroke, action::NEXT_TAB) {
+                self.next_tab();
+                self.sync_cmdline(global);
+                self.refresh_status_line(profile);
+            } else if key_map.is_bound(key_stroke, action::PREV_TAB) {
+                self.prev_tab();
+                self.sync_cmdline(global);
+                self.refresh_status_line(profile);
+            } else if key_map.is_bound(key_stroke, action::CLOSE_TAB) {
+                self.close_tab_or_confirm(close_requested);
+                self.sync_cmdline(global);
+                if !self.close_requested {
                     self.refresh_status_line(profile);
                 }
-            } else if let Some(list) = self.lists.last_mut() {
-                list.handle_key(key_stroke, key_map);
+            } else if global.bottom_state == BottomState::Nothing
+                && key_map.is_bound(key_stroke, action::EXPORT_MATCHES)
+            {
+                global.bottom_state = BottomState::ExportPath;
+                let path = global
+                    .export_path
+                    .as_ref()
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_else(|| "errors.err".to_string());
+                global.bottom_edit_field.activate(&mut global.cursor, path);
+            } else if global.bottom_state == BottomState::Nothing
+                && key_map.is_bound(key_stroke, action::RENAME_TAB)
+            {
+                global.bottom_state = BottomState::TabName;
+                global
+                    .bottom_edit_field
+                    .activate(&mut global.cursor, self.tab().title());
+            } else if let Some(list) = self.tab_mut().current_list_mut() {
+                list.list.handle_key(key_stroke, key_map);
                 self.refresh_status_line(profile);
             }
         }
     }
 }
 
-/// Expands tab ('\t' 0x9) characters within an input string
-/// into a variable amount of spaces.
-///
-/// ```text
-/// |--------|    |--------|--------| 8 spaces/tab (tabsize = 8)
-/// |\t      | => |........|        | 8 spaces
-/// |\ta     | => |........|a       | 8 spaces + "a"
-/// |aaa\t   | => |aaa.....|        | "aaa" + 5 spaces
-/// ```
-///
-fn expand_tabs(input: &str, tabsize: usize) -> String {
-    if tabsize == 0 {
-        return input.replace('\t', "");
-    }
-    if tabsize == 1 {
-        return input.replace('\t', " ");
-    }
-
-    let mut result =
-        String::with_capacity(input.len() + (tabsize - 1) * input.matches('\t').count());
-    let mut char_count = 0;
-
-    for c in input.chars() {
-        if c == '\t' {
-            let space_count = tabsize - (char_count % tabsize);
-            char_count += space_count;
-            result.extend(std::iter::repeat(' ').take(space_count));
-        } else {
-            char_count += 1;
-            result.push(c);
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pcre2::bytes::RegexBuilder;
+
+    #[test]
+    fn test_render_cmdline_quotes_captures() {
+        let regex = RegexBuilder::new()
+            .utf(true)
+            .build(r"^(.+):(\d+):")
+            .unwrap();
+        let shell = shell::Shell::default();
+        let line = "$(touch pwned) it's.rs:42: oops".as_bytes();
+
+        assert_eq!(
+            render_cmdline(line, r"vim +\2 \1", &regex, &shell),
+            Some(Ok(r"vim +42 '$(touch pwned) it'\''s.rs'".to_string()))
+        );
+        assert_eq!(
+            render_cmdline(line, r#"echo "\!1""#, &regex, &shell),
+            Some(Ok(r#"echo "$(touch pwned) it's.rs""#.to_string()))
+        );
+        assert_eq!(
+            render_cmdline(line, r"argv: vim +\2 \1", &regex, &shell),
+            Some(Ok(r#"argv: vim +42 "$(touch pwned) it's.rs""#.to_string()))
+        );
+        assert_eq!(render_cmdline(b"nope", r"vim \1", &regex, &shell), None);
+
+        // NOTE: the file name is not valid UTF-8
+        let cmdline = render_cmdline(b"caf\xe9.c:1: oops", r"printf %s \1", &regex, &shell)
+            .unwrap()
+            .unwrap();
+        assert_eq!(cmdline, r#"printf %s caf"$(printf '\351')".c"#);
+        let output = shell.command(&cmdline).unwrap().output().unwrap();
+        assert_eq!(output.stdout, b"caf\xe9.c");
     }
 
-    result
+    #[test]
+    fn test_preview_location() {
+        let location = |pattern: &str, line: &str| {
+            let regex = RegexBuilder::new().utf(true).build(pattern).unwrap();
+            let matcher = Matcher::single(regex);
+            let record = matcher.find_record(&[line], 0)?;
+            preview_location(&record)
+        };
+        assert_eq!(
+            location(r"(\/?\b.*?):(\d+):", "src/main.rs:42: oops"),
+            Some(("src/main.rs".to_string(), 42))
+        );
+        assert_eq!(
+            location(r"^(?<file>\S+\.c): error", "main.c: error"),
+            Some(("main.c".to_string(), 1))
+        );
+        assert_eq!(location(r"^error: (.*)", "error: "), None);
+    }
+
+    #[test]
+    fn test_tabs() {
+        let mut output_buffer = OutputBuffer::new(shell::Shell::default());
+        output_buffer.tab_mut().name = Some("a".to_string());
+        output_buffer.new_tab(Some("c".to_string()));
+        output_buffer.prev_tab();
+        output_buffer.new_tab(Some("b".to_string()));
+        let titles = |output_buffer: &OutputBuffer| {
+            output_buffer
+                .tabs
+                .iter()
+                .map(Tab::title)
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(titles(&output_buffer), vec!["a", "b", "c"]);
+        assert_eq!(output_buffer.current_tab, 1);
+
+        output_buffer.next_tab();
+        output_buffer.next_tab();
+        assert_eq!(output_buffer.current_tab, 0);
+        output_buffer.prev_tab();
+        assert_eq!(output_buffer.current_tab, 2);
+
+        output_buffer.close_tab();
+        assert_eq!(titles(&output_buffer), vec!["a", "b"]);
+        assert_eq!(output_buffer.current_tab, 1);
+        output_buffer.prev_tab();
+        output_buffer.close_tab();
+        assert_eq!(titles(&output_buffer), vec!["b"]);
+        assert_eq!(output_buffer.current_tab, 0);
+        output_buffer.close_tab();
+        assert_eq!(titles(&output_buffer), vec!["cm"]);
+        assert_eq!(output_buffer.current_tab, 0);
+    }
+
+    #[test]
+    fn test_close_running_tab() {
+        let mut output_buffer = OutputBuffer::new(shell::Shell::default());
+        output_buffer.new_tab(Some("sleep 10".to_string()));
+        output_buffer.run_cmdline("sleep 10".to_string(), false);
+        assert!(output_buffer.tab().child.is_some());
+
+        output_buffer.close_tab_or_confirm(false);
+        assert_eq!(output_buffer.tabs.len(), 2);
+        assert!(output_buffer.close_requested);
+
+        output_buffer.close_tab_or_confirm(true);
+        assert_eq!(output_buffer.tabs.len(), 1);
+        assert_eq!(output_buffer.current_tab, 0);
+    }
+
+    #[test]
+    fn test_byte_match_to_char_match() {
+        let line = "caf\u{FFFD}.c:3:";
+        let char_mat = byte_match_to_char_match(&ByteMatch { start: 0, end: 8 }, line).unwrap();
+        assert_eq!((char_mat.start, char_mat.end), (0, 6));
+        // NOTE: the offsets of the raw bytes may land inside of the
+        // replacement character of the lossy text
+        assert!(byte_match_to_char_match(&ByteMatch { start: 0, end: 4 }, line).is_none());
+    }
 }
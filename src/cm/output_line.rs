@@ -0,0 +1,216 @@
+use super::*;
+use pcre2::bytes::Regex;
+use std::fmt;
+
+/// The output stream of the child process a line was received from
+#[derive(PartialEq, Clone, Copy)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// A single line of the output of a child process with all of the
+/// escape sequences stripped from it. The colors the child asked
+/// for are kept in [OutputLine::spans](struct.OutputLine.html#structfield.spans)
+#[derive(Clone)]
+pub struct OutputLine {
+    /// the line with every invalid UTF-8 sequence replaced by U+FFFD
+    pub text: String,
+    /// the original bytes of the line. `None` if they are a valid UTF-8,
+    /// in which case they are exactly the bytes of
+    /// [OutputLine::text](struct.OutputLine.html#structfield.text).
+    pub raw: Option<Vec<u8>>,
+    /// byte ranges within [OutputLine::text](struct.OutputLine.html#structfield.text)
+    pub spans: Vec<ansi::Span>,
+    /// `None` for the lines generated by cm itself
+    pub stream: Option<Stream>,
+}
+
+impl OutputLine {
+    pub fn new(text: String, spans: Vec<ansi::Span>, stream: Option<Stream>) -> Self {
+        Self {
+            text,
+            raw: None,
+            spans,
+            stream,
+        }
+    }
+
+    /// Creates a line from the output of a child that is not guaranteed to be
+    /// a valid UTF-8. The `spans` are byte ranges within the `bytes`.
+    pub fn from_bytes(bytes: Vec<u8>, spans: Vec<ansi::Span>, stream: Option<Stream>) -> Self {
+        match String::from_utf8(bytes) {
+            Ok(text) => Self::new(text, spans, stream),
+            Err(err) => {
+                let bytes = err.into_bytes();
+                let spans = spans
+                    .into_iter()
+                    .map(|span| ansi::Span {
+                        start: lossy_offset(&bytes, span.start),
+                        end: lossy_offset(&bytes, span.end),
+                        style: span.style,
+                    })
+                    .collect();
+                Self {
+                    text: String::from_utf8_lossy(&bytes).into_owned(),
+                    raw: Some(bytes),
+                    spans,
+                    stream,
+                }
+            }
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match &self.raw {
+            Some(raw) => raw,
+            None => self.text.as_bytes(),
+        }
+    }
+
+    /// Matches the `regex` against the original bytes of the line. PCRE2
+    /// refuses to match invalid UTF-8 in the UTF mode, in which case the
+    /// lossy text is used instead.
+    pub fn is_match(&self, regex: &Regex) -> bool {
+        regex
+            .is_match(self.as_bytes())
+            .or_else(|_| regex.is_match(self.text.as_bytes()))
+            .unwrap_or(false)
+    }
+}
+
+impl AsRef<str> for OutputLine {
+    fn as_ref(&self) -> &str {
+        &self.text
+    }
+}
+
+/// A line the [Matcher](struct.Matcher.html) finds the records in. The
+/// `AsRef<str>` of the line is its lossy text.
+pub trait RawLine: AsRef<str> {
+    /// the original bytes of the line
+    fn raw_bytes(&self) -> &[u8] {
+        self.as_ref().as_bytes()
+    }
+}
+
+impl RawLine for OutputLine {
+    fn raw_bytes(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl RawLine for &str {}
+
+impl RawLine for String {}
+
+/// Maps the `offset` within the `bytes` onto the offset within
+/// `String::from_utf8_lossy(bytes)`. Offsets that point inside of
+/// an invalid sequence are mapped onto the beginning of its
+/// replacement character.
+pub fn lossy_offset(bytes: &[u8], offset: usize) -> usize {
+    let mut bytes_begin = 0;
+    let mut lossy_begin = 0;
+
+    for chunk in bytes.utf8_chunks() {
+        let valid = chunk.valid().len();
+        if offset <= bytes_begin + valid {
+            return lossy_begin + offset - bytes_begin;
+        }
+        bytes_begin += valid;
+        lossy_begin += valid;
+
+        let invalid = chunk.invalid().len();
+        if offset < bytes_begin + invalid {
+            return lossy_begin;
+        }
+        bytes_begin += invalid;
+        if invalid > 0 {
+            lossy_begin += char::REPLACEMENT_CHARACTER.len_utf8();
+        }
+    }
+
+    lossy_begin
+}
+
+/// The inverse of [lossy_offset](fn.lossy_offset.html): maps the
+/// `offset` within `String::from_utf8_lossy(bytes)` onto the offset
+/// within the `bytes`. Offsets that point inside of a replacement
+/// character are mapped onto the beginning of its invalid sequence.
+pub fn raw_offset(bytes: &[u8], offset: usize) -> usize {
+    let mut bytes_begin = 0;
+    let mut lossy_begin = 0;
+
+    for chunk in bytes.utf8_chunks() {
+        let valid = chunk.valid().len();
+        if offset <= lossy_begin + valid {
+            return bytes_begin + offset - lossy_begin;
+        }
+        bytes_begin += valid;
+        lossy_begin += valid;
+
+        let invalid = chunk.invalid().len();
+        if invalid > 0 {
+            if offset < lossy_begin + char::REPLACEMENT_CHARACTER.len_utf8() {
+                return bytes_begin;
+            }
+            lossy_begin += char::REPLACEMENT_CHARACTER.len_utf8();
+        }
+        bytes_begin += invalid;
+    }
+
+    bytes_begin
+}
+
+impl From<String> for OutputLine {
+    fn from(text: String) -> Self {
+        Self::new(text, Vec::new(), None)
+    }
+}
+
+impl fmt::Display for OutputLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bytes() {
+        let line = OutputLine::from_bytes(b"caf\xe9: \xe2\x82".to_vec(), Vec::new(), None);
+        assert_eq!(line.text, "caf\u{FFFD}: \u{FFFD}");
+        assert_eq!(line.as_bytes(), b"caf\xe9: \xe2\x82");
+
+        let line = OutputLine::from_bytes("caf\u{e9}".as_bytes().to_vec(), Vec::new(), None);
+        assert!(line.raw.is_none());
+        assert_eq!(line.as_bytes(), "caf\u{e9}".as_bytes());
+    }
+
+    #[test]
+    fn test_lossy_offset() {
+        let bytes = b"a\xe9b\xe2\x82";
+        assert_eq!(lossy_offset(bytes, 0), 0);
+        assert_eq!(lossy_offset(bytes, 1), 1);
+        assert_eq!(lossy_offset(bytes, 2), 4);
+        assert_eq!(lossy_offset(bytes, 3), 5);
+        assert_eq!(lossy_offset(bytes, 4), 5);
+        assert_eq!(lossy_offset(bytes, 5), 8);
+    }
+
+    #[test]
+    fn test_raw_offset() {
+        let bytes = b"a\xe9b\xe2\x82";
+        for offset in 0..=bytes.len() {
+            let lossy = lossy_offset(bytes, offset);
+            assert_eq!(lossy_offset(bytes, raw_offset(bytes, lossy)), lossy);
+        }
+        assert_eq!(raw_offset(bytes, 1), 1);
+        assert_eq!(raw_offset(bytes, 2), 1);
+        assert_eq!(raw_offset(bytes, 4), 2);
+        assert_eq!(raw_offset(bytes, 5), 3);
+        assert_eq!(raw_offset(bytes, 8), 5);
+    }
+}
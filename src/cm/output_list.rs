@@ -0,0 +1,359 @@
+use super::*;
+use libc::{localtime_r, time_t, tm};
+use std::mem::MaybeUninit;
+use std::process::ExitStatus;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum StreamFilter {
+    Both,
+    Stdout,
+    Stderr,
+}
+
+impl StreamFilter {
+    pub fn next(self) -> Self {
+        match self {
+            StreamFilter::Both => StreamFilter::Stdout,
+            StreamFilter::Stdout => StreamFilter::Stderr,
+            StreamFilter::Stderr => StreamFilter::Both,
+        }
+    }
+
+    /// The lines that did not come from the child (like the `PID: ...`
+    /// header) are always accepted.
+    pub fn accepts(self, line: &OutputLine) -> bool {
+        match (self, line.stream) {
+            (StreamFilter::Both, _) | (_, None) => true,
+            (StreamFilter::Stdout, Some(stream)) => stream == Stream::Stdout,
+            (StreamFilter::Stderr, Some(stream)) => stream == Stream::Stderr,
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            StreamFilter::Both => "stdout and stderr",
+            StreamFilter::Stdout => "stdout only",
+            StreamFilter::Stderr => "stderr only",
+        }
+    }
+}
+
+/// Formats the `time` as `YYYY-MM-DD HH:MM:SS` in the local timezone
+fn format_time(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or(0) as time_t;
+    let mut local = MaybeUninit::<tm>::uninit();
+    if unsafe { localtime_r(&secs, local.as_mut_ptr()) }.is_null() {
+        return "????-??-?? ??:??:??".to_string();
+    }
+    let local = unsafe { local.assume_init() };
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        local.tm_year + 1900,
+        local.tm_mon + 1,
+        local.tm_mday,
+        local.tm_hour,
+        local.tm_min,
+        local.tm_sec
+    )
+}
+
+/// Describes the state of a child for the user: `running`, its exit
+/// code or `killed` if it was terminated by a signal.
+pub fn describe_exit_status(running: bool, exit_status: Option<ExitStatus>) -> String {
+    if running {
+        "running".to_string()
+    } else {
+        match exit_status.map(|status| status.code()) {
+            Some(Some(code)) => code.to_string(),
+            Some(None) => "killed".to_string(),
+            None => "-".to_string(),
+        }
+    }
+}
+
+/// A number that no other change of any [OutputList](struct.OutputList.html)
+/// was given
+fn next_generation() -> u64 {
+    static GENERATION: AtomicU64 = AtomicU64::new(0);
+    GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// The severities of the records of an [OutputList](struct.OutputList.html)
+/// counted so far
+struct CountedSeverities {
+    /// see [Matcher::fingerprint](struct.Matcher.html#method.fingerprint)
+    fingerprint: String,
+    /// the amount of the items whose records are counted
+    scanned: usize,
+    counts: SeverityCounts,
+}
+
+/// The [QuickfixEntry](struct.QuickfixEntry.html)s of the records of an
+/// [OutputList](struct.OutputList.html) collected so far
+struct CollectedEntries {
+    /// see [Matcher::fingerprint](struct.Matcher.html#method.fingerprint)
+    fingerprint: String,
+    /// the amount of the items whose records are collected
+    scanned: usize,
+    entries: Vec<QuickfixEntry>,
+}
+
+/// The output of a single run of a command. Keeps all of the received
+/// lines, while the [OutputList::list](struct.OutputList.html#structfield.list)
+/// contains only the lines accepted by the current [StreamFilter](enum.StreamFilter.html).
+pub struct OutputList {
+    pub lines: Vec<OutputLine>,
+    pub list: ItemList<OutputLine>,
+    pub filter: StreamFilter,
+    /// the command line that produced the output. `None` for the lists
+    /// generated by cm itself, like the welcome message.
+    pub cmdline: Option<String>,
+    pub pid: Option<u32>,
+    pub started: SystemTime,
+    /// `None` while the child is still running
+    pub exit_status: Option<ExitStatus>,
+    counted: Option<CountedSeverities>,
+    collected: Option<CollectedEntries>,
+    /// changes whenever the items change. No two lists ever share it, so
+    /// it identifies both the list and its items.
+    generation: u64,
+}
+
+impl OutputList {
+    pub fn new() -> Self {
+        Self {
+            lines: Vec::new(),
+            list: ItemList::new(),
+            filter: StreamFilter::Both,
+            cmdline: None,
+            pid: None,
+            started: SystemTime::now(),
+            exit_status: None,
+            counted: None,
+            collected: None,
+            generation: next_generation(),
+        }
+    }
+
+    /// See [OutputList::generation](struct.OutputList.html#structfield.generation)
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// A single line summary of the run for the history pane. See
+    /// [Tab::history](struct.Tab.html#structfield.history)
+    pub fn summary(&self, running: bool) -> String {
+        format!(
+            "{}  PID: {}  [{}]  {} lines  {}",
+            format_time(self.started),
+            self.pid
+                .map(|pid| pid.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            describe_exit_status(running, self.exit_status),
+            self.lines.len(),
+            self.cmdline.as_deref().unwrap_or("<cm>")
+        )
+    }
+
+    pub fn push(&mut self, line: OutputLine) {
+        if self.filter.accepts(&line) {
+            self.list.items.push(line.clone());
+            self.generation = next_generation();
+        }
+        self.lines.push(line);
+    }
+
+    pub fn replace(&mut self, index: usize, line: OutputLine) {
+        if self.filter.accepts(&self.lines[index]) {
+            let item = if index + 1 == self.lines.len() {
+                self.list.items.len() - 1
+            } else {
+                self.lines[..index]
+                    .iter()
+                    .filter(|line| self.filter.accepts(line))
+                    .count()
+            };
+            self.list.items[item] = line.clone();
+            self.generation = next_generation();
+            if self
+                .counted
+                .as_ref()
+                .is_some_and(|counted| item < counted.scanned)
+            {
+                self.counted = None;
+            }
+            if self
+                .collected
+                .as_ref()
+                .is_some_and(|collected| item < collected.scanned)
+            {
+                self.collected = None;
+            }
+        }
+        self.lines[index] = line;
+    }
+
+    pub fn set_filter(&mut self, filter: StreamFilter) {
+        // NOTE: trying to keep the cursor on the same line or at least
+        // somewhere close to it
+        let current = self
+            .lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| self.filter.accepts(line))
+            .nth(self.list.cursor_y)
+            .map(|(index, _)| index)
+            .unwrap_or(0);
+
+        self.filter = filter;
+        self.counted = None;
+        self.collected = None;
+        self.generation = next_generation();
+        self.list.items = self
+            .lines
+            .iter()
+            .filter(|line| filter.accepts(line))
+            .cloned()
+            .collect();
+        self.list.cursor_y = self.lines[..current]
+            .iter()
+            .filter(|line| filter.accepts(line))
+            .count();
+        if self.list.cursor_y >= self.list.items.len() {
+            self.list.cursor_y = self.list.items.len().saturating_sub(1);
+        }
+    }
+
+    /// Counts the records of the `matcher` in the items by their
+    /// severity. The output is only ever appended to, so the records that
+    /// can not change anymore are counted once and only the last few
+    /// items are rescanned on every call.
+    pub fn severity_counts(&mut self, matcher: &Matcher) -> SeverityCounts {
+        let fingerprint = matcher.fingerprint();
+        let items = &self.list.items;
+        let counted = match &mut self.counted {
+            Some(counted) if counted.fingerprint == fingerprint => counted,
+            counted => counted.insert(CountedSeverities {
+                fingerprint,
+                scanned: 0,
+                counts: SeverityCounts::default(),
+            }),
+        };
+
+        // NOTE: the record starting at an item is settled once all of the
+        // lines it may span are received and the last one of them is not
+        // the last item, which may still be rewritten by `\r`
+        let settled = items.len().saturating_sub(matcher.window());
+        if counted.scanned < settled {
+            let counts = matcher.count_severities(items, counted.scanned..settled);
+            counted.counts.merge(counts);
+            counted.scanned = settled;
+        }
+
+        let mut counts = counted.counts;
+        counts.merge(matcher.count_severities(items, counted.scanned..items.len()));
+        counts
+    }
+
+    /// The [QuickfixEntry](struct.QuickfixEntry.html)s of the records of
+    /// the `matcher` in the items, see [sort_entries](fn.sort_entries.html).
+    /// The settled records are collected once the same way
+    /// [severity_counts](struct.OutputList.html#method.severity_counts)
+    /// counts them.
+    pub fn quickfix_entries(&mut self, matcher: &Matcher) -> Vec<QuickfixEntry> {
+        let fingerprint = matcher.fingerprint();
+        let items = &self.list.items;
+        let collected = match &mut self.collected {
+            Some(collected) if collected.fingerprint == fingerprint => collected,
+            collected => collected.insert(CollectedEntries {
+                fingerprint,
+                scanned: 0,
+                entries: Vec::new(),
+            }),
+        };
+
+        let settled = items.len().saturating_sub(matcher.window());
+        if collected.scanned < settled {
+            let entries = find_entries(matcher, items, collected.scanned..settled);
+            collected.entries.extend(entries);
+            collected.scanned = settled;
+        }
+
+        let mut entries = collected.entries.clone();
+        entries.extend(find_entries(matcher, items, collected.scanned..items.len()));
+        sort_entries(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(text: &str, stream: Option<Stream>) -> OutputLine {
+        OutputLine::new(text.to_string(), Vec::new(), stream)
+    }
+
+    fn items(list: &OutputList) -> Vec<&str> {
+        list.list
+            .items
+            .iter()
+            .map(|item| item.text.as_str())
+            .collect()
+    }
+
+    fn output_list() -> OutputList {
+        let mut list = OutputList::new();
+        list.push(line("PID: 1", None));
+        list.push(line("out1", Some(Stream::Stdout)));
+        list.push(line("err1", Some(Stream::Stderr)));
+        list.push(line("out2", Some(Stream::Stdout)));
+        list.push(line("err2", Some(Stream::Stderr)));
+        list
+    }
+
+    #[test]
+    fn test_set_filter() {
+        let mut list = output_list();
+        list.list.cursor_y = 3;
+        list.set_filter(StreamFilter::Stdout);
+        assert_eq!(items(&list), vec!["PID: 1", "out1", "out2"]);
+        assert_eq!(list.list.cursor_y, 2);
+
+        // NOTE: the hidden line the cursor was on is skipped to the next
+        // accepted one
+        list.set_filter(StreamFilter::Stderr);
+        assert_eq!(items(&list), vec!["PID: 1", "err1", "err2"]);
+        assert_eq!(list.list.cursor_y, 2);
+
+        list.set_filter(StreamFilter::Both);
+        assert_eq!(list.list.cursor_y, 4);
+
+        // NOTE: no line to skip to after the last one
+        list.set_filter(StreamFilter::Stdout);
+        assert_eq!(list.list.cursor_y, 2);
+    }
+
+    #[test]
+    fn test_replace() {
+        let mut list = output_list();
+        list.set_filter(StreamFilter::Stdout);
+
+        list.replace(2, line("err1!", Some(Stream::Stderr)));
+        assert_eq!(items(&list), vec!["PID: 1", "out1", "out2"]);
+        list.replace(3, line("out2!", Some(Stream::Stdout)));
+        assert_eq!(items(&list), vec!["PID: 1", "out1", "out2!"]);
+        list.replace(4, line("err2!", Some(Stream::Stderr)));
+        assert_eq!(items(&list), vec!["PID: 1", "out1", "out2!"]);
+
+        list.set_filter(StreamFilter::Both);
+        assert_eq!(
+            items(&list),
+            vec!["PID: 1", "out1", "err1!", "out2!", "err2!"]
+        );
+    }
+}
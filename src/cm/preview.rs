@@ -0,0 +1,178 @@
+use super::*;
+use ncurses::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The width of a tab character in the previewed files
+const TAB_WIDTH: usize = 4;
+
+/// The files larger than that are not previewed, they would be read into
+/// memory as a whole just to show a few of their lines
+const MAX_PREVIEW_SIZE: u64 = 16 * 1024 * 1024;
+
+/// The file the lines of the [Preview](struct.Preview.html) were read
+/// from. The file is read again only if it was modified since.
+struct Source {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    lines: Result<Vec<String>, String>,
+}
+
+/// Shows the file captured by the `file` group of the current record
+/// scrolled to the captured `line`, so the matches can be triaged without
+/// running the command on them.
+pub struct Preview {
+    pub visible: bool,
+    source: Option<Source>,
+}
+
+/// The index of the first line to show, so the `line` is in the middle of
+/// `h` lines unless it is too close to either end of the file
+fn preview_top(line: usize, len: usize, h: usize) -> usize {
+    line.saturating_sub(h / 2).min(len.saturating_sub(h))
+}
+
+/// The lines of the file at `path` ready to be shown. Only the regular
+/// files of at most `max_size` bytes are read, the path is captured from
+/// the output and a FIFO or a device there would block cm.
+fn read_lines(path: &Path, max_size: u64) -> Result<Vec<String>, String> {
+    let fail = |err: &dyn std::fmt::Display| format!("Could not open {}: {}", path.display(), err);
+    let metadata = fs::metadata(path).map_err(|err| fail(&err))?;
+    if !metadata.is_file() {
+        return Err(fail(&"Not a regular file"));
+    }
+    if metadata.len() > max_size {
+        return Err(fail(&format!(
+            "The file is too large to preview ({} bytes)",
+            metadata.len()
+        )));
+    }
+    let bytes = fs::read(path).map_err(|err| fail(&err))?;
+    Ok(String::from_utf8_lossy(&bytes)
+        .lines()
+        .map(|line| line.replace('\t', &" ".repeat(TAB_WIDTH)).replace('\r', ""))
+        .collect())
+}
+
+impl Preview {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            source: None,
+        }
+    }
+
+    fn load(&mut self, path: &Path) -> &Result<Vec<String>, String> {
+        let modified = fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        let stale = match &self.source {
+            Some(source) => source.path != path || source.modified != modified,
+            None => true,
+        };
+        if stale {
+            let lines = read_lines(path, MAX_PREVIEW_SIZE);
+            self.source = Some(Source {
+                path: path.to_path_buf(),
+                modified,
+                lines,
+            });
+        }
+        &self.source.as_ref().unwrap().lines
+    }
+
+    /// Renders the `line` (1-based) of the `file` along with the lines
+    /// around it. The first row of the `rect` is the title.
+    pub fn render(&mut self, rect: Rect, location: Option<(&str, usize)>) {
+        let Rect { x, y, w, h } = rect;
+        if h == 0 || w == 0 {
+            return;
+        }
+
+        let title = match location {
+            Some((file, line)) => format!(" {}:{} ", file, line),
+            None => " No location under the cursor ".to_string(),
+        };
+        mv(y as i32, x as i32);
+        attron(COLOR_PAIR(UNFOCUSED_CURSOR_PAIR));
+        addstr(unicode::width_substr(&title, 0..w).map_or("", |(title, _)| title));
+        attroff(COLOR_PAIR(UNFOCUSED_CURSOR_PAIR));
+
+        let (file, line) = match location {
+            Some(location) => location,
+            None => return,
+        };
+        let lines = match self.load(Path::new(file)) {
+            Ok(lines) => lines,
+            Err(err) => {
+                if h > 1 {
+                    mv((y + 1) as i32, x as i32);
+                    attron(COLOR_PAIR(STATUS_ERROR_PAIR));
+                    addstr(unicode::width_substr(err, 0..w).map_or("", |(err, _)| err));
+                    attroff(COLOR_PAIR(STATUS_ERROR_PAIR));
+                }
+                return;
+            }
+        };
+
+        let index = line.saturating_sub(1);
+        let body_h = h - 1;
+        let top = preview_top(index, lines.len(), body_h);
+        let number_width = lines.len().to_string().len();
+        for (i, text) in lines.iter().enumerate().skip(top).take(body_h) {
+            let row = format!("{:>width$} {}", i + 1, text, width = number_width);
+            let (row, (_, right)) = unicode::width_substr(&row, 0..w).unwrap_or(("", (0, w)));
+            let pair = if i == index {
+                CURSOR_PAIR
+            } else {
+                REGULAR_PAIR
+            };
+            mv((y + 1 + i - top) as i32, x as i32);
+            attron(COLOR_PAIR(pair));
+            addstr(row);
+            if i == index {
+                addstr(&" ".repeat(right));
+            }
+            attroff(COLOR_PAIR(pair));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_top() {
+        assert_eq!(preview_top(0, 100, 10), 0);
+        assert_eq!(preview_top(50, 100, 10), 45);
+        assert_eq!(preview_top(99, 100, 10), 90);
+        assert_eq!(preview_top(3, 5, 10), 0);
+    }
+
+    #[test]
+    fn test_read_lines() {
+        let path = std::env::temp_dir().join(format!("cm-preview-{}.c", std::process::id()));
+        fs::write(&path, "int\tmain() {\r\n}\n").unwrap();
+        let lines = read_lines(&path, MAX_PREVIEW_SIZE);
+        let too_large = read_lines(&path, 4);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            lines,
+            Ok(vec!["int    main() {".to_string(), "}".to_string()])
+        );
+        assert_eq!(
+            too_large,
+            Err(format!(
+                "Could not open {}: The file is too large to preview (16 bytes)",
+                path.display()
+            ))
+        );
+        assert_eq!(
+            read_lines(Path::new("/dev/null"), MAX_PREVIEW_SIZE),
+            Err("Could not open /dev/null: Not a regular file".to_string())
+        );
+    }
+}
@@ -1,16 +1,61 @@
 use super::*;
 use pcre2::bytes::{Regex, RegexBuilder};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::io;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::string::ToString;
+use std::time::{Duration, Instant};
+
+/// How often [select_best_regex](struct.Profile.html#method.select_best_regex)
+/// scores the regexes again while the output keeps coming
+const BEST_REGEX_INTERVAL: Duration = Duration::from_millis(500);
 
 pub struct Profile {
     pub regex_list: StringList,
     pub cmd_list: StringList,
+    pub rule_list: RuleList,
     // TODO(#231): there is no UI for customizing Profile::shell similar to how we customize key bindings
     pub shell: PathBuf,
+    /// the arguments the command line is passed to the
+    /// [Profile::shell](struct.Profile.html#structfield.shell) with
+    pub shell_args: Vec<String>,
+    /// Run the child processes in a pseudo-terminal instead of a pipe
+    pub pty: bool,
+    /// Save the output of the tabs into the session file on exit. See
+    /// [session::path](session/fn.path.html)
+    pub session: bool,
+    /// How the regex the output is matched with is chosen
+    pub regex_selection: RegexSelection,
+    /// The maximum amount of lines a multi-line record may span. See
+    /// [is_multiline](fn.is_multiline.html)
+    pub record_window: usize,
     pub key_map: KeyMap,
+    /// the regex provided through the CLI (`--regex`). Used instead of
+    /// the current item of the [regex_list](struct.Profile.html#structfield.regex_list)
+    /// until the user picks a regex there. Never saved.
+    pub regex_override: Option<String>,
+    /// the command provided through the CLI (`--cmd`). Used instead of
+    /// the current item of the [cmd_list](struct.Profile.html#structfield.cmd_list)
+    /// until the user picks a command there. Never saved.
+    pub cmd_override: Option<String>,
+    /// the regex picked by [select_best_regex](struct.Profile.html#method.select_best_regex).
+    /// Kept apart from the cursor of the [regex_list](struct.Profile.html#structfield.regex_list),
+    /// so it is not saved.
+    best_regex: Option<String>,
+    /// the [generation](struct.OutputList.html#method.generation) of the
+    /// output list and the regexes the [best_regex](struct.Profile.html#structfield.best_regex)
+    /// was picked for and when, so it is not picked again until something
+    /// changes
+    best_scored_on: Option<(u64, Vec<String>, Instant)>,
+    /// the compiled regexes of the [regex_list](struct.Profile.html#structfield.regex_list),
+    /// so they are not compiled again on every render
+    compiled: RefCell<HashMap<String, Result<Regex, pcre2::Error>>>,
+}
+
+fn build_regex(pattern: &str) -> Result<Regex, pcre2::Error> {
+    RegexBuilder::new().utf(true).ucp(true).build(pattern)
 }
 
 impl Profile {
@@ -18,25 +63,38 @@ impl Profile {
         Self {
             regex_list: StringList::new(),
             cmd_list: StringList::new(),
+            rule_list: RuleList::new(),
             shell: PathBuf::new(),
+            shell_args: vec!["-c".to_string()],
+            pty: false,
+            session: false,
+            regex_selection: RegexSelection::Manual,
+            record_window: DEFAULT_RECORD_WINDOW,
             key_map: KeyMap::new(),
+            regex_override: None,
+            cmd_override: None,
+            best_regex: None,
+            best_scored_on: None,
+            compiled: RefCell::new(HashMap::new()),
         }
     }
 
-    pub fn from_file(input: Vec<String>, file_path: &Path) -> Self {
+    /// Parses the configuration lines without the version line. The
+    /// errors are reported instead of panicking, so a broken configuration
+    /// file does not bring cm down.
+    pub fn parse(input: &[String], file_path: &Path) -> Result<Self, String> {
         let mut result = Profile::new();
-        let mut regex_count = 0;
-        let mut cmd_count = 0;
+        let mut regex_count: usize = 0;
+        let mut cmd_count: usize = 0;
         for (i, line) in input.iter().map(|x| x.trim_start()).enumerate() {
-            // TODO(#128): profile parsing errors should be application error messages instead of Rust panics
-            let fail = |message| panic!("{}:{}: {}", file_path.display(), i + 1, message);
+            let fail = |message: &str| format!("{}:{}: {}", file_path.display(), i + 1, message);
 
             if !line.is_empty() {
                 let (key, value) = config::split_key_value(line)
-                    .unwrap_or_else(|| fail("Invalid configuration line"));
+                    .ok_or_else(|| fail("Invalid configuration line"))?;
 
                 if key.is_empty() {
-                    fail("Key is not provided");
+                    return Err(fail("Key is not provided"));
                 }
 
                 match key {
@@ -48,35 +106,91 @@ impl Profile {
                         cmd_count += 1;
                         result.cmd_list.list.items.push(value.to_string());
                     }
+                    "rule" => {
+                        if result.rule_list.find(value).is_some() {
+                            return Err(fail("Duplicate rule name"));
+                        }
+                        result
+                            .rule_list
+                            .rules
+                            .push(Rule::new(value.to_string(), String::new()));
+                    }
+                    "builtin_rule" => {
+                        if result.rule_list.find(value).is_some() {
+                            return Err(fail("Duplicate rule name"));
+                        }
+                        let rule = catalog::rule(value).ok_or_else(|| {
+                            fail(&format!(
+                                "Unknown built-in rule `{}`. Expected one of: {}",
+                                value,
+                                catalog::ENTRIES
+                                    .iter()
+                                    .map(|entry| entry.name)
+                                    .collect::<Vec<_>>()
+                                    .join(", ")
+                            ))
+                        })?;
+                        result.rule_list.rules.push(rule);
+                    }
+                    "rule_description" | "rule_regex" | "rule_cmd" | "rule_severity" => {
+                        let rule = result.rule_list.rules.last_mut().ok_or_else(|| {
+                            fail(&format!("`{}` without a `rule` line above it", key))
+                        })?;
+                        match key {
+                            "rule_description" => rule.description = Some(value.to_string()),
+                            "rule_regex" => rule.regex = value.to_string(),
+                            "rule_severity" => {
+                                rule.severity = Some(
+                                    value
+                                        .parse()
+                                        .map_err(|_| fail("Expected error, warning or info"))?,
+                                )
+                            }
+                            _ => rule.cmds.push(value.to_string()),
+                        }
+                    }
                     "shell" => {
                         result.shell.push(value);
                     }
+                    "shell_args" => {
+                        result.shell_args =
+                            shell::split(value).map_err(|_| fail("Unterminated quote"))?
+                    }
+                    "pty" => {
+                        result.pty = value.parse::<bool>().map_err(|_| fail("Not a boolean"))?
+                    }
+                    "session" => {
+                        result.session = value.parse::<bool>().map_err(|_| fail("Not a boolean"))?
+                    }
+                    "regex_selection" => {
+                        result.regex_selection = value
+                            .parse()
+                            .map_err(|_| fail("Expected manual, best or per_line"))?
+                    }
+                    "record_window" => {
+                        result.record_window =
+                            value.parse::<usize>().map_err(|_| fail("Not a number"))?
+                    }
                     "current_regex" => {
                         if value.is_empty() {
-                            fail("Value is not provided");
+                            return Err(fail("Value is not provided"));
                         }
                         result.regex_list.list.cursor_y =
-                            value.parse::<usize>().unwrap_or_else(|_| {
-                                fail("Not a number");
-                                0
-                            })
+                            value.parse::<usize>().map_err(|_| fail("Not a number"))?
                     }
                     "current_cmd" => {
                         if value.is_empty() {
-                            fail("Value is not provided");
+                            return Err(fail("Value is not provided"));
                         }
                         result.cmd_list.list.cursor_y =
-                            value.parse::<usize>().unwrap_or_else(|_| {
-                                fail("Not a number");
-                                0
-                            })
+                            value.parse::<usize>().map_err(|_| fail("Not a number"))?
                     }
                     key => {
                         if value.is_empty() {
-                            fail("Value is not provided");
+                            return Err(fail("Value is not provided"));
                         }
-                        let key_stroke = KeyStroke::from_str(key).unwrap();
-                        let action = action::from_str(value).unwrap();
+                        let key_stroke = KeyStroke::from_str(key).map_err(|err| fail(&err))?;
+                        let action = action::from_str(value).map_err(|err| fail(&err))?;
                         result.key_map.bind(key_stroke, action);
                     }
                 }
@@ -84,16 +198,29 @@ impl Profile {
         }
 
         // NOTE: regex_count-1 converts value from count to 0-based index
-        if result.regex_list.list.cursor_y > regex_count - 1 {
-            result.regex_list.list.cursor_y = regex_count - 1;
+        if result.regex_list.list.cursor_y > regex_count.saturating_sub(1) {
+            result.regex_list.list.cursor_y = regex_count.saturating_sub(1);
         }
 
         // NOTE: cmd_count-1 converts value from count to 0-based index
-        if result.cmd_list.list.cursor_y > cmd_count - 1 {
-            result.cmd_list.list.cursor_y = cmd_count - 1;
+        if result.cmd_list.list.cursor_y > cmd_count.saturating_sub(1) {
+            result.cmd_list.list.cursor_y = cmd_count.saturating_sub(1);
         }
 
-        result
+        if let Some(rule) = result
+            .rule_list
+            .rules
+            .iter()
+            .find(|rule| rule.regex.is_empty())
+        {
+            return Err(format!(
+                "{}: rule `{}` does not have a `rule_regex`",
+                file_path.display(),
+                rule.name
+            ));
+        }
+
+        Ok(result)
     }
 
     pub fn to_file<F: io::Write>(&self, stream: &mut F) -> io::Result<()> {
@@ -107,7 +234,38 @@ impl Profile {
             writeln!(stream, "cmds = {}", cmd)?;
         }
 
+        for rule in self.rule_list.rules.iter() {
+            if rule.builtin {
+                writeln!(stream, "builtin_rule = {}", rule.name)?;
+                continue;
+            }
+            writeln!(stream, "rule = {}", rule.name)?;
+            if let Some(description) = &rule.description {
+                writeln!(stream, "rule_description = {}", description)?;
+            }
+            writeln!(stream, "rule_regex = {}", rule.regex)?;
+            if let Some(severity) = rule.severity {
+                writeln!(stream, "rule_severity = {}", severity)?;
+            }
+            for cmd in rule.cmds.iter() {
+                writeln!(stream, "rule_cmd = {}", cmd)?;
+            }
+        }
+
         writeln!(stream, "shell = {}", self.shell.display())?;
+        writeln!(
+            stream,
+            "shell_args = {}",
+            self.shell_args
+                .iter()
+                .map(|arg| shell::quote_word(arg))
+                .collect::<Vec<String>>()
+                .join(" ")
+        )?;
+        writeln!(stream, "pty = {}", self.pty)?;
+        writeln!(stream, "session = {}", self.session)?;
+        writeln!(stream, "regex_selection = {}", self.regex_selection)?;
+        writeln!(stream, "record_window = {}", self.record_window)?;
 
         writeln!(stream, "current_regex = {}", self.regex_list.list.cursor_y)?;
         writeln!(stream, "current_cmd = {}", self.cmd_list.list.cursor_y)?;
@@ -117,28 +275,224 @@ impl Profile {
         Ok(())
     }
 
+    /// The pattern of the current regex while the
+    /// [regex_list](struct.Profile.html#structfield.regex_list) is not
+    /// being edited
+    fn current_pattern(&self) -> Option<&String> {
+        let best = match self.regex_selection {
+            RegexSelection::Best => self.best_regex.as_ref(),
+            _ => None,
+        };
+        self.regex_override
+            .as_ref()
+            .or(best)
+            .or_else(|| self.regex_list.current_item())
+    }
+
+    /// The regexes the [RegexSelection::Best](enum.RegexSelection.html#variant.Best)
+    /// and [RegexSelection::PerLine](enum.RegexSelection.html#variant.PerLine)
+    /// choose from: the [regex_list](struct.Profile.html#structfield.regex_list)
+    /// followed by the regexes of the enabled built-in rules
+    fn candidates(&self) -> Vec<&String> {
+        let mut result: Vec<&String> = self.regex_list.list.items.iter().collect();
+        for rule in self.rule_list.rules.iter().filter(|rule| rule.builtin) {
+            if !result.contains(&&rule.regex) {
+                result.push(&rule.regex);
+            }
+        }
+        result
+    }
+
+    /// Compiles the `pattern` once. The regexes that are not among the
+    /// [candidates](struct.Profile.html#method.candidates) anymore are
+    /// forgotten.
+    fn compile(&self, pattern: &str) -> Result<Regex, pcre2::Error> {
+        let mut compiled = self.compiled.borrow_mut();
+        if !compiled.contains_key(pattern) {
+            let candidates = self.candidates();
+            compiled.retain(|pattern, _| {
+                candidates.contains(&pattern) || self.regex_override.as_ref() == Some(pattern)
+            });
+            compiled.insert(pattern.to_string(), build_regex(pattern));
+        }
+        compiled[pattern].clone()
+    }
+
     pub fn current_regex(&self) -> Option<Result<Regex, pcre2::Error>> {
         match self.regex_list.state {
-            StringListState::Navigate => self
-                .regex_list
-                .current_item()
-                .map(|s| RegexBuilder::new().utf(true).ucp(true).build(&s)),
-            StringListState::Editing { .. } => Some(
-                RegexBuilder::new()
-                    .utf(true)
-                    .ucp(true)
-                    .build(&self.regex_list.edit_field.buffer),
-            ),
+            StringListState::Navigate => self.current_pattern().map(|s| self.compile(s)),
+            StringListState::Editing { .. } => {
+                Some(build_regex(&self.regex_list.edit_field.buffer))
+            }
         }
     }
 
+    /// The regexes the output is matched with according to the
+    /// [regex_selection](struct.Profile.html#structfield.regex_selection).
+    /// While a regex is being edited only that regex is used, so the
+    /// effect of the editing is visible.
+    pub fn matcher(&self) -> Option<Result<Matcher, pcre2::Error>> {
+        let rule_of = |regex: &str| self.rule_list.rules.iter().find(|rule| rule.regex == regex);
+        let candidates = self.candidates();
+        let matcher = match (self.regex_selection, &self.regex_list.state) {
+            (RegexSelection::PerLine, StringListState::Navigate)
+                if !candidates.is_empty() && self.regex_override.is_none() =>
+            {
+                let current = self.current_pattern();
+                let patterns = candidates
+                    .into_iter()
+                    .map(|regex| {
+                        let rule = rule_of(regex);
+                        // NOTE: the matches of the current regex use the
+                        // current command, the matches of the rest use the
+                        // command of their rule if they have one
+                        let cmd = if Some(regex) == current {
+                            None
+                        } else {
+                            rule.and_then(|rule| rule.cmds.first().cloned())
+                        };
+                        self.compile(regex).map(|regex| Pattern {
+                            cmd,
+                            severity: rule.and_then(|rule| rule.severity),
+                            ..Pattern::new(regex)
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>();
+                Some(patterns.map(Matcher::new))
+            }
+            _ => self.current_regex().map(|regex| {
+                regex.map(|regex| {
+                    let severity = rule_of(regex.as_str()).and_then(|rule| rule.severity);
+                    Matcher::new(vec![Pattern {
+                        severity,
+                        ..Pattern::new(regex)
+                    }])
+                })
+            }),
+        };
+        matcher.map(|matcher| matcher.map(|matcher| matcher.with_window(self.record_window)))
+    }
+
+    /// Makes the regex that matches the most of the items of the
+    /// `output_list` the current one if the [regex_selection](struct.Profile.html#structfield.regex_selection)
+    /// is [RegexSelection::Best](enum.RegexSelection.html#variant.Best).
+    /// The regexes are scored again right away when they change, and at
+    /// most every [BEST_REGEX_INTERVAL](constant.BEST_REGEX_INTERVAL.html)
+    /// when the output changes. Returns `true` if the current regex has
+    /// changed.
+    pub fn select_best_regex(&mut self, output_list: &OutputList) -> bool {
+        if self.regex_selection != RegexSelection::Best
+            || self.regex_list.state != StringListState::Navigate
+            || self.regex_override.is_some()
+        {
+            return false;
+        }
+
+        let candidates: Vec<String> = self.candidates().into_iter().cloned().collect();
+        let generation = output_list.generation();
+        if let Some((scored_generation, scored_candidates, scored_at)) = &self.best_scored_on {
+            if *scored_candidates == candidates
+                && (*scored_generation == generation || scored_at.elapsed() < BEST_REGEX_INTERVAL)
+            {
+                return false;
+            }
+        }
+
+        let regexes = candidates
+            .iter()
+            .map(|regex| self.compile(regex).ok())
+            .collect::<Vec<_>>();
+        let current = self
+            .current_pattern()
+            .and_then(|pattern| candidates.iter().position(|x| x == pattern))
+            .unwrap_or(self.regex_list.list.cursor_y);
+        let best = best_regex(
+            &regexes,
+            &output_list.list.items,
+            current,
+            self.record_window,
+        )
+        .and_then(|best| candidates.get(best))
+        .cloned();
+        // NOTE: the previous pick stays while nothing matches, unless it
+        // is not among the regexes anymore
+        let previous = self.best_regex.take();
+        self.best_regex =
+            best.or_else(|| previous.clone().filter(|regex| candidates.contains(regex)));
+        self.best_scored_on = Some((generation, candidates, Instant::now()));
+        self.best_regex != previous
+    }
+
     pub fn current_cmd(&self) -> Option<String> {
         match self.cmd_list.state {
-            StringListState::Navigate => self.cmd_list.current_item().map(String::from),
+            StringListState::Navigate => self
+                .cmd_override
+                .as_ref()
+                .or_else(|| self.cmd_list.current_item())
+                .cloned(),
             StringListState::Editing { .. } => Some(self.cmd_list.edit_field.buffer.clone()),
         }
     }
 
+    /// Selects the regex and the commands of the rule at `index` in the
+    /// [regex_list](struct.Profile.html#structfield.regex_list) and the
+    /// [cmd_list](struct.Profile.html#structfield.cmd_list). The ones that
+    /// are not there yet are added.
+    pub fn apply_rule(&mut self, index: usize) {
+        if let Some(rule) = self.rule_list.rules.get(index) {
+            self.regex_override = None;
+            self.cmd_override = None;
+            self.regex_list.select_or_push(&rule.regex);
+            // NOTE: going backwards leaves the first command selected
+            for cmd in rule.cmds.iter().rev() {
+                self.cmd_list.select_or_push(cmd);
+            }
+        }
+    }
+
+    /// Saves the current regex and command as a new rule
+    pub fn add_rule(&mut self, name: String) -> Result<(), String> {
+        let regex = self
+            .current_pattern()
+            .ok_or_else(|| "There is no regex to make a rule of".to_string())?;
+        let mut rule = Rule::new(name, regex.clone());
+        rule.cmds.extend(
+            self.cmd_override
+                .as_ref()
+                .or_else(|| self.cmd_list.current_item())
+                .cloned(),
+        );
+        self.rule_list.add(rule)
+    }
+
+    pub fn render_rules(&mut self, rect: Rect, focused: bool) {
+        let current_regex = self.current_pattern().cloned();
+        self.rule_list.refresh(current_regex.as_deref());
+        self.rule_list.list.render(rect, focused);
+    }
+
+    pub fn handle_rules_key(&mut self, key_stroke: KeyStroke, global: &mut Global) {
+        let key_map = &self.key_map;
+        if !global.handle_key(key_stroke, key_map) {
+            if key_map.is_bound(key_stroke, action::ACCEPT) {
+                self.apply_rule(self.rule_list.list.cursor_y);
+            } else if key_map.is_bound(key_stroke, action::INSERT_AFTER_ITEM) {
+                global.bottom_state = BottomState::RuleName;
+                global
+                    .bottom_edit_field
+                    .activate(&mut global.cursor, String::new());
+            } else if key_map.is_bound(key_stroke, action::DELETE) {
+                self.rule_list.delete_current();
+            } else if key_map.is_bound(key_stroke, action::BACK) {
+                global.mode = Mode::Output;
+            } else {
+                let current_regex = self.current_pattern().cloned();
+                self.rule_list.refresh(current_regex.as_deref());
+                self.rule_list.list.handle_key(key_stroke, key_map);
+            }
+        }
+    }
+
     pub fn initial() -> Self {
         let mut result = Self::new();
         result
@@ -157,3 +511,136 @@ impl Profile {
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_rules() {
+        let input = lines(
+            "regexs = (\\S+):(\\d+):
+cmds = vim +\\2 \\1
+current_regex = 0
+current_cmd = 0
+rule = rustc
+rule_description = Errors of rustc
+rule_regex = --> (\\S+):(\\d+):(\\d+)
+rule_severity = error
+rule_cmd = vim +\\2 \\1
+rule_cmd = code -g \\1:\\2:\\3
+rule = grep
+rule_regex = (\\S+):(\\d+):
+builtin_rule = gcc",
+        );
+        let mut profile = Profile::parse(&input, Path::new("cm.conf")).unwrap();
+        assert_eq!(profile.rule_list.rules.len(), 3);
+        assert!(profile.rule_list.rules[2].builtin);
+        assert_eq!(profile.regex_list.list.items.len(), 1);
+        assert_eq!(
+            profile.candidates().last(),
+            Some(&&profile.rule_list.rules[2].regex)
+        );
+        assert_eq!(
+            profile.rule_list.rules[0].cmds,
+            vec!["vim +\\2 \\1", "code -g \\1:\\2:\\3"]
+        );
+        assert_eq!(profile.rule_list.rules[1].description, None);
+        assert_eq!(profile.rule_list.rules[0].severity, Some(Severity::Error));
+
+        profile.apply_rule(0);
+        assert_eq!(
+            profile.regex_list.current_item().map(String::as_str),
+            Some("--> (\\S+):(\\d+):(\\d+)")
+        );
+        assert_eq!(profile.current_cmd().as_deref(), Some("vim +\\2 \\1"));
+        assert_eq!(profile.cmd_list.list.items.len(), 2);
+
+        assert!(profile.add_rule("rustc".to_string()).is_err());
+        profile.add_rule("copy".to_string()).unwrap();
+
+        let mut output = Vec::new();
+        profile.to_file(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("\nbuiltin_rule = gcc\n"));
+        let reloaded = Profile::parse(
+            &output.lines().skip(1).map(String::from).collect::<Vec<_>>(),
+            Path::new("cm.conf"),
+        )
+        .unwrap();
+        assert_eq!(reloaded.rule_list.rules, profile.rule_list.rules);
+    }
+
+    #[test]
+    fn test_select_best_regex() {
+        let mut profile = Profile::new();
+        profile.regex_list.list.items = vec![
+            r"^(\S+\.c):(\d+):".to_string(),
+            r"--> (\S+):(\d+):(\d+)".to_string(),
+        ];
+        profile.regex_selection = RegexSelection::Best;
+        let line = |text: &str| OutputLine::new(text.to_string(), Vec::new(), None);
+        let mut output_list = OutputList::new();
+        output_list.push(line("error: oops"));
+        output_list.push(line("  --> src/main.rs:4:5"));
+
+        assert!(profile.select_best_regex(&output_list));
+        assert_eq!(
+            profile.current_regex().unwrap().unwrap().as_str(),
+            r"--> (\S+):(\d+):(\d+)"
+        );
+        assert_eq!(profile.regex_list.list.cursor_y, 0);
+        assert!(!profile.select_best_regex(&output_list));
+
+        // NOTE: the changes of the output are scored only once the
+        // interval has passed
+        output_list.push(line("main.c:1: error"));
+        output_list.push(line("main.c:2: error"));
+        assert!(!profile.select_best_regex(&output_list));
+        if let Some((_, _, scored_at)) = profile.best_scored_on.as_mut() {
+            *scored_at -= BEST_REGEX_INTERVAL;
+        }
+        assert!(profile.select_best_regex(&output_list));
+        assert_eq!(
+            profile.current_regex().unwrap().unwrap().as_str(),
+            r"^(\S+\.c):(\d+):"
+        );
+
+        // NOTE: the changes of the regexes are scored right away
+        profile.regex_list.list.items.remove(0);
+        assert!(profile.select_best_regex(&output_list));
+        assert_eq!(
+            profile.current_regex().unwrap().unwrap().as_str(),
+            r"--> (\S+):(\d+):(\d+)"
+        );
+    }
+
+    #[test]
+    fn test_overrides() {
+        let mut profile = Profile::initial();
+        profile.rule_list.rules.push(catalog::rule("gcc").unwrap());
+        let saved = |profile: &Profile| {
+            let mut output = Vec::new();
+            profile.to_file(&mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        let before = saved(&profile);
+
+        profile.regex_override = Some("(\\S+)@(\\d+)".to_string());
+        profile.cmd_override = Some("less +\\2 \\1".to_string());
+        assert_eq!(
+            profile.current_regex().unwrap().unwrap().as_str(),
+            "(\\S+)@(\\d+)"
+        );
+        assert_eq!(profile.current_cmd().as_deref(), Some("less +\\2 \\1"));
+        assert_eq!(saved(&profile), before);
+
+        profile.apply_rule(0);
+        assert_eq!(profile.regex_override, None);
+        assert_eq!(profile.cmd_override, None);
+    }
+}
@@ -0,0 +1,107 @@
+use libc::*;
+use std::fs::File;
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::ptr::{null, null_mut};
+
+fn winsize(w: usize, h: usize) -> winsize {
+    winsize {
+        ws_row: h as c_ushort,
+        ws_col: w as c_ushort,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    }
+}
+
+fn mark_cloexec(fd: RawFd) {
+    unsafe {
+        let flags = libc::fcntl(fd, F_GETFD, 0);
+        libc::fcntl(fd, F_SETFD, flags | FD_CLOEXEC);
+    }
+}
+
+/// Opens a new pseudo-terminal pair with the window size of `w`x`h`
+/// characters. Returns `(master, slave)`.
+///
+/// The output post-processing of `\n` into `\r\n` is disabled on the
+/// slave, so the lines we read from the master look exactly like the
+/// lines we would read from a plain pipe.
+// TODO(#94): pty::open does not work on Windows
+pub fn open(w: usize, h: usize) -> io::Result<(File, File)> {
+    let mut master: c_int = -1;
+    let mut slave: c_int = -1;
+    let size = winsize(w, h);
+
+    unsafe {
+        if openpty(&mut master, &mut slave, null_mut(), null(), &size) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let master = File::from_raw_fd(master);
+        let slave = File::from_raw_fd(slave);
+        mark_cloexec(master.as_raw_fd());
+        mark_cloexec(slave.as_raw_fd());
+
+        let mut term = MaybeUninit::<termios>::uninit();
+        if tcgetattr(slave.as_raw_fd(), term.as_mut_ptr()) == 0 {
+            let mut term = term.assume_init();
+            term.c_oflag &= !ONLCR;
+            tcsetattr(slave.as_raw_fd(), TCSANOW, &term);
+        }
+
+        Ok((master, slave))
+    }
+}
+
+/// Informs the pseudo-terminal about the new window size. The kernel
+/// delivers `SIGWINCH` to the foreground process group of the terminal.
+pub fn set_window_size<Fd: AsRawFd>(master: &Fd, w: usize, h: usize) -> io::Result<()> {
+    let size = winsize(w, h);
+    if unsafe { ioctl(master.as_raw_fd(), TIOCSWINSZ, &size) } < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Makes the calling process a session leader with the slave of the
+/// pseudo-terminal (already dup-ed into the stdin) as its controlling
+/// terminal. Supposed to be called in the child between fork and exec.
+pub fn make_controlling_terminal() -> io::Result<()> {
+    unsafe {
+        if setsid() < 0 || ioctl(0, TIOCSCTTY as _, 0) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    fn window_size(slave: &File) -> (usize, usize) {
+        let mut size = winsize(0, 0);
+        assert_eq!(
+            unsafe { ioctl(slave.as_raw_fd(), TIOCGWINSZ, &mut size) },
+            0
+        );
+        (size.ws_col as usize, size.ws_row as usize)
+    }
+
+    #[test]
+    fn test_open() {
+        let (mut master, mut slave) = open(80, 24).unwrap();
+        assert_eq!(window_size(&slave), (80, 24));
+
+        slave.write_all(b"hello\n").unwrap();
+        let mut buffer = [0; 16];
+        let n = master.read(&mut buffer).unwrap();
+        assert_eq!(&buffer[..n], b"hello\n");
+
+        set_window_size(&master, 120, 40).unwrap();
+        assert_eq!(window_size(&slave), (120, 40));
+    }
+}
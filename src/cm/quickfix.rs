@@ -0,0 +1,282 @@
+use super::*;
+use pcre2::bytes::Regex;
+use std::collections::HashSet;
+use std::ops::Range;
+
+/// The index of the group of the `regex` that captures the part of the
+/// location called `name`: the group of that name, otherwise the `nth`
+/// unnamed group. That way the regexes written for the `\1 \2` templates
+/// like `(\/?\b.*?):(\d+):` have a location too.
+fn location_group(regex: &Regex, name: &str, nth: usize) -> Option<usize> {
+    let names = regex.capture_names();
+    names
+        .iter()
+        .position(|other| other.as_deref() == Some(name))
+        .or_else(|| {
+            (1..names.len())
+                .filter(|index| names[*index].is_none())
+                .nth(nth)
+        })
+}
+
+/// A record of the output as listed by the [Quickfix](struct.Quickfix.html).
+/// The location is taken from the `file`, `line` and `col` groups of the
+/// regex (or the first three unnamed ones if it does not name them), the
+/// message from the `message` group if there is one.
+#[derive(Debug, PartialEq, Clone)]
+pub struct QuickfixEntry {
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    pub col: Option<usize>,
+    pub message: String,
+    pub severity: Option<Severity>,
+    /// the index of the item of the output list the record starts at
+    pub index: usize,
+}
+
+impl QuickfixEntry {
+    pub fn from_record(record: &Record, index: usize) -> Self {
+        let text = record.text.as_str();
+        let caps = record.regex.captures(text.as_bytes()).ok().flatten();
+        let capture = |index: Option<usize>| {
+            caps.as_ref()
+                .zip(index)
+                .and_then(|(caps, index)| caps.get(index))
+                .and_then(|mat| text.get(mat.start()..mat.end()))
+                .filter(|value| !value.is_empty())
+        };
+        let location = |name: &str, nth: usize| capture(location_group(record.regex, name, nth));
+
+        // NOTE: without the `message` group the first line of a multi-line
+        // record is usually the message (`error[E0308]: mismatched types`),
+        // while a single-line record usually carries the message after
+        // the location (`main.c:3:10: error: expected ';'`)
+        let match_end = caps
+            .as_ref()
+            .and_then(|caps| caps.get(0))
+            .map_or(0, |mat| mat.end());
+        let message = match caps.as_ref().and_then(|caps| caps.name("message")) {
+            Some(message) => text.get(message.start()..message.end()).unwrap_or(""),
+            None if record.len > 1 => text.lines().next().unwrap_or(""),
+            None => match text.get(match_end..).map(str::trim) {
+                Some(rest) if !rest.is_empty() => rest,
+                _ => text,
+            },
+        };
+
+        Self {
+            file: location("file", 0).map(String::from),
+            line: location("line", 1).and_then(|line| line.parse().ok()),
+            col: location("col", 2).and_then(|col| col.parse().ok()),
+            message: message.trim().to_string(),
+            severity: record.severity,
+            index,
+        }
+    }
+
+    /// `file:line:col` omitting the parts that were not captured
+    pub fn location(&self) -> String {
+        let mut result = self.file.clone().unwrap_or_default();
+        for number in [self.line, self.col].iter().flatten() {
+            result.push_str(&format!(":{}", number));
+        }
+        result
+    }
+
+    pub fn summary(&self) -> String {
+        let severity = self
+            .severity
+            .map(|severity| format!("{}: ", severity))
+            .unwrap_or_default();
+        match self.location() {
+            location if location.is_empty() => format!("{}{}", severity, self.message),
+            location => format!("{}: {}{}", location, severity, self.message),
+        }
+    }
+}
+
+/// Turns the records of the `matcher` starting at the `range` of the
+/// `lines` into the entries in the order of the output
+pub fn find_entries<S: RawLine>(
+    matcher: &Matcher,
+    lines: &[S],
+    range: Range<usize>,
+) -> Vec<QuickfixEntry> {
+    range
+        .filter_map(|index| {
+            let record = matcher.find_record(lines, index)?;
+            Some(QuickfixEntry::from_record(&record, index))
+        })
+        .collect()
+}
+
+/// Finds the records of the `matcher` in the `lines` and turns them into
+/// the entries, one per location and message, sorted by the file.
+pub fn collect_entries<S: RawLine>(matcher: &Matcher, lines: &[S]) -> Vec<QuickfixEntry> {
+    sort_entries(find_entries(matcher, lines, 0..lines.len()))
+}
+
+/// Removes the duplicates of the `entries` and sorts them by the file
+pub fn sort_entries(entries: Vec<QuickfixEntry>) -> Vec<QuickfixEntry> {
+    let mut seen = HashSet::new();
+    let mut entries: Vec<QuickfixEntry> = entries
+        .into_iter()
+        .filter(|entry| seen.insert(entry.summary()))
+        .collect();
+    // NOTE: the sort is stable, so the entries of the same location
+    // stay in the order of the output
+    entries.sort_by(|a, b| {
+        (a.file.is_none(), &a.file, a.line, a.col).cmp(&(b.file.is_none(), &b.file, b.line, b.col))
+    });
+    entries
+}
+
+/// The list of the records found in the current output list, one per
+/// location and message, sorted by the file.
+pub struct Quickfix {
+    pub entries: Vec<QuickfixEntry>,
+    pub list: ItemList<String>,
+    /// the [fingerprint](struct.Matcher.html#method.fingerprint) of the
+    /// matcher and the [generation](struct.OutputList.html#method.generation)
+    /// of the output list the entries were collected from
+    refreshed_on: Option<(String, u64)>,
+}
+
+impl Quickfix {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            list: ItemList::new(),
+            refreshed_on: None,
+        }
+    }
+
+    pub fn current(&self) -> Option<&QuickfixEntry> {
+        self.entries.get(self.list.cursor_y)
+    }
+
+    /// Rebuilds the entries from the records of the `matcher` in the
+    /// `output_list` keeping the cursor on the same entry, so the entries
+    /// can be browsed while the output is still coming. Nothing is done
+    /// unless the matcher or the output list have changed.
+    pub fn refresh(&mut self, matcher: Option<&Matcher>, output_list: Option<&mut OutputList>) {
+        let source = matcher.zip(output_list);
+        let refreshed_on = source
+            .as_ref()
+            .map(|(matcher, output_list)| (matcher.fingerprint(), output_list.generation()));
+        if refreshed_on == self.refreshed_on {
+            return;
+        }
+        self.refreshed_on = refreshed_on;
+
+        let current = self.current().map(QuickfixEntry::summary);
+
+        self.entries = match source {
+            Some((matcher, output_list)) => output_list.quickfix_entries(matcher),
+            None => Vec::new(),
+        };
+
+        self.list.items = self.entries.iter().map(QuickfixEntry::summary).collect();
+        if let Some(current) = current {
+            if let Some(index) = self.list.items.iter().position(|item| *item == current) {
+                self.list.cursor_y = index;
+            }
+        }
+        if self.list.cursor_y >= self.list.items.len() {
+            self.list.cursor_y = self.list.items.len().saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pcre2::bytes::RegexBuilder;
+
+    #[test]
+    fn test_refresh() {
+        let line = |text: &str| OutputLine::new(text.to_string(), Vec::new(), None);
+        let output_list = |texts: &[&str]| {
+            let mut output_list = OutputList::new();
+            for text in texts.iter() {
+                output_list.push(line(text));
+            }
+            output_list
+        };
+        let lines = [
+            "src/b.c:3:10: error: expected ';'",
+            "src/a.c:12:5: warning: unused variable 'x'",
+            "In file included from src/a.c:1:",
+            "src/b.c:3:10: error: expected ';'",
+            "src/a.c:2:1: error: unknown type name 'foo'",
+        ];
+        let regex = RegexBuilder::new()
+            .utf(true)
+            .build(catalog::find("gcc").unwrap().regex)
+            .unwrap();
+        let matcher = Matcher::single(regex);
+
+        let mut quickfix = Quickfix::new();
+        quickfix.refresh(Some(&matcher), Some(&mut output_list(&lines)));
+        assert_eq!(
+            quickfix.list.items,
+            vec![
+                "src/a.c:2:1: error: unknown type name 'foo'",
+                "src/a.c:12:5: warning: unused variable 'x'",
+                "src/b.c:3:10: error: expected ';'",
+            ]
+        );
+        assert_eq!(
+            quickfix
+                .entries
+                .iter()
+                .map(|entry| entry.index)
+                .collect::<Vec<_>>(),
+            vec![4, 1, 0]
+        );
+
+        quickfix.list.cursor_y = 1;
+        let mut streaming = output_list(&lines[..2]);
+        quickfix.refresh(Some(&matcher), Some(&mut streaming));
+        assert_eq!(quickfix.current().map(|entry| entry.index), Some(1));
+
+        // NOTE: the lines that come later and the ones rewritten by `\r`
+        // are picked up
+        streaming.push(line("src/c.c:1:1: error: oops 10%"));
+        quickfix.refresh(Some(&matcher), Some(&mut streaming));
+        streaming.replace(2, line("src/c.c:1:1: error: oops 100%"));
+        quickfix.refresh(Some(&matcher), Some(&mut streaming));
+        assert_eq!(
+            quickfix.list.items,
+            vec![
+                "src/a.c:12:5: warning: unused variable 'x'",
+                "src/b.c:3:10: error: expected ';'",
+                "src/c.c:1:1: error: oops 100%",
+            ]
+        );
+        assert_eq!(quickfix.current().map(|entry| entry.index), Some(1));
+    }
+
+    #[test]
+    fn test_numbered_groups() {
+        let entry = |pattern: &str, line: &str| {
+            let regex = RegexBuilder::new().utf(true).build(pattern).unwrap();
+            let matcher = Matcher::single(regex);
+            let record = matcher.find_record(&[line], 0).unwrap();
+            QuickfixEntry::from_record(&record, 0)
+        };
+
+        let default = entry(r"(\/?\b.*?):(\d+):", "src/main.rs:42: oops");
+        assert_eq!(default.location(), "src/main.rs:42");
+        assert_eq!(default.message, "oops");
+
+        let mixed = entry(
+            r"^(?<severity>error|warning): (\S+):(\d+):(\d+)",
+            "error: a.c:1:2",
+        );
+        assert_eq!(mixed.location(), "a.c:1:2");
+
+        let named = entry(r"^(\w+) (?<file>\S+) (?<line>\d+)", "at b.c 7");
+        assert_eq!(named.location(), "b.c:7");
+    }
+}
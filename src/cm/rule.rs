@@ -0,0 +1,106 @@
+use super::*;
+
+/// A regex bound to the commands that make sense for its matches, so
+/// switching from, say, grep output to rustc output is a single step
+/// instead of picking the regex and the command separately.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rule {
+    pub name: String,
+    pub description: Option<String>,
+    pub regex: String,
+    /// the first command is selected when the rule is applied
+    pub cmds: Vec<String>,
+    /// the severity of the matches of the regex that do not capture one.
+    /// See [Severity::classify](enum.Severity.html#method.classify)
+    pub severity: Option<Severity>,
+    /// the rule comes from the [catalog](catalog/index.html) and is saved
+    /// to the configuration by its name only
+    pub builtin: bool,
+}
+
+impl Rule {
+    pub fn new(name: String, regex: String) -> Self {
+        Self {
+            name,
+            description: None,
+            regex,
+            cmds: Vec::new(),
+            severity: None,
+            builtin: false,
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{}  {}",
+            self.name,
+            self.description.as_deref().unwrap_or(&self.regex)
+        )
+    }
+}
+
+pub struct RuleList {
+    pub rules: Vec<Rule>,
+    pub list: ItemList<String>,
+}
+
+impl RuleList {
+    pub fn new() -> Self {
+        Self {
+            rules: Vec::new(),
+            list: ItemList::new(),
+        }
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.rules.iter().position(|rule| rule.name == name)
+    }
+
+    /// Adds the `rule` after the cursor and moves the cursor to it
+    pub fn add(&mut self, rule: Rule) -> Result<(), String> {
+        if rule.name.is_empty() {
+            return Err("The name of a rule can not be empty".to_string());
+        }
+        if self.find(&rule.name).is_some() {
+            return Err(format!("Rule `{}` already exists", rule.name));
+        }
+
+        if self.rules.is_empty() {
+            self.rules.push(rule);
+            self.list.cursor_y = 0;
+        } else {
+            self.list.cursor_y += 1;
+            self.rules.insert(self.list.cursor_y, rule);
+        }
+        Ok(())
+    }
+
+    pub fn delete_current(&mut self) {
+        if self.list.cursor_y < self.rules.len() {
+            self.rules.remove(self.list.cursor_y);
+            if self.list.cursor_y >= self.rules.len() && !self.rules.is_empty() {
+                self.list.cursor_y = self.rules.len() - 1;
+            }
+        }
+    }
+
+    /// Syncs the items of the list with the rules marking the ones whose
+    /// regex is the `current_regex` with `*`
+    pub fn refresh(&mut self, current_regex: Option<&str>) {
+        self.list.items = self
+            .rules
+            .iter()
+            .map(|rule| {
+                format!(
+                    "{} {}",
+                    if Some(rule.regex.as_str()) == current_regex {
+                        "*"
+                    } else {
+                        " "
+                    },
+                    rule.summary()
+                )
+            })
+            .collect();
+    }
+}
@@ -0,0 +1,278 @@
+use super::*;
+use std::env::var;
+use std::io;
+use std::os::unix::process::ExitStatusExt;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::time::{Duration, UNIX_EPOCH};
+
+pub const VERSION: usize = 1;
+
+/// How many of the most recent lists of every tab are saved
+pub const MAX_LISTS_PER_TAB: usize = 16;
+
+/// `$XDG_STATE_HOME/cm/session` or `~/.local/state/cm/session` if
+/// `$XDG_STATE_HOME` is not set.
+pub fn path() -> Option<PathBuf> {
+    const SESSION_FILE_NAME: &str = "session";
+    let xdg_state_dir = var("XDG_STATE_HOME").map(PathBuf::from);
+    let home_state_dir = var("HOME")
+        .map(PathBuf::from)
+        .map(|x| x.join(".local").join("state"));
+    xdg_state_dir
+        .or(home_state_dir)
+        .ok()
+        .map(|p| p.join("cm").join(SESSION_FILE_NAME))
+}
+
+/// Escapes the `text` so it survives [config::split_key_value](../config/fn.split_key_value.html):
+/// the newlines are turned into `\n` and the whitespace at the edges is
+/// protected from trimming.
+fn escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let last = text.chars().count().saturating_sub(1);
+    for (i, c) in text.chars().enumerate() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            ' ' if i == 0 || i == last => result.push_str("\\s"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+fn unescape(text: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('\\') => result.push('\\'),
+                Some('n') => result.push('\n'),
+                Some('r') => result.push('\r'),
+                Some('t') => result.push('\t'),
+                Some('s') => result.push(' '),
+                Some(c) => return Err(format!("Unknown escape sequence `\\{}`", c)),
+                None => return Err("Unfinished escape sequence".to_string()),
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    Ok(result)
+}
+
+fn non_empty(value: String) -> Option<String> {
+    if value.is_empty() {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+pub fn save<F: io::Write>(output_buffer: &OutputBuffer, stream: &mut F) -> io::Result<()> {
+    writeln!(stream, "version = {}", VERSION)?;
+    writeln!(stream, "current_tab = {}", output_buffer.current_tab)?;
+
+    for tab in output_buffer.tabs.iter() {
+        let skip = tab.lists.len().saturating_sub(MAX_LISTS_PER_TAB);
+        writeln!(
+            stream,
+            "tab = {}",
+            escape(tab.name.as_deref().unwrap_or(""))
+        )?;
+        writeln!(
+            stream,
+            "cmdline = {}",
+            escape(tab.user_provided_cmdline.as_deref().unwrap_or(""))
+        )?;
+        writeln!(stream, "current = {}", tab.current.saturating_sub(skip))?;
+
+        for list in tab.lists.iter().skip(skip) {
+            writeln!(
+                stream,
+                "list = {}",
+                escape(list.cmdline.as_deref().unwrap_or(""))
+            )?;
+            if let Some(pid) = list.pid {
+                writeln!(stream, "pid = {}", pid)?;
+            }
+            if let Ok(started) = list.started.duration_since(UNIX_EPOCH) {
+                writeln!(stream, "started = {}", started.as_secs())?;
+            }
+            if let Some(status) = list.exit_status {
+                writeln!(stream, "exit_status = {}", status.into_raw())?;
+            }
+            writeln!(stream, "cursor = {}", list.list.cursor_y)?;
+            writeln!(stream, "scroll_x = {}", list.list.scroll_x)?;
+            writeln!(stream, "scroll_y = {}", list.list.scroll_y)?;
+            for line in list.lines.iter() {
+                let key = match line.stream {
+                    Some(Stream::Stdout) => "stdout",
+                    Some(Stream::Stderr) => "stderr",
+                    None => "line",
+                };
+                writeln!(stream, "{} = {}", key, escape(&line.text))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Restores the tabs saved by [session::save](fn.save.html). Returns
+/// the tabs and the index of the current one. None of the restored tabs
+/// have a running child.
+pub fn load(input: &str) -> Result<(Vec<Tab>, usize), String> {
+    let mut tabs: Vec<Tab> = Vec::new();
+    let mut current_tab = 0;
+
+    for (i, line) in input.lines().map(|x| x.trim_start()).enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+
+        let fail = |message: String| format!("session:{}: {}", i + 1, message);
+        let (key, value) = config::split_key_value(line)
+            .ok_or_else(|| fail("Invalid session line".to_string()))?;
+        let number = || {
+            value
+                .parse::<usize>()
+                .map_err(|_| fail("Not a number".to_string()))
+        };
+
+        match key {
+            "version" => {
+                if number()? != VERSION {
+                    return Err(fail(format!("Unsupported session version {}", value)));
+                }
+            }
+            "current_tab" => current_tab = number()?,
+            "tab" => {
+                let mut tab = Tab::new(None);
+                tab.name = non_empty(unescape(value).map_err(fail)?);
+                tabs.push(tab);
+            }
+            key => {
+                let tab = tabs
+                    .last_mut()
+                    .ok_or_else(|| fail(format!("`{}` outside of a tab", key)))?;
+                match key {
+                    "cmdline" => {
+                        tab.user_provided_cmdline = non_empty(unescape(value).map_err(fail)?)
+                    }
+                    "current" => tab.current = number()?,
+                    "list" => {
+                        let mut list = OutputList::new();
+                        list.cmdline = non_empty(unescape(value).map_err(fail)?);
+                        tab.lists.push(list);
+                    }
+                    key => {
+                        let list = tab
+                            .lists
+                            .last_mut()
+                            .ok_or_else(|| fail(format!("`{}` outside of a list", key)))?;
+                        match key {
+                            "pid" => list.pid = Some(number()? as u32),
+                            "started" => {
+                                list.started = UNIX_EPOCH + Duration::from_secs(number()? as u64)
+                            }
+                            "exit_status" => {
+                                let raw = value
+                                    .parse::<i32>()
+                                    .map_err(|_| fail("Not a number".to_string()))?;
+                                list.exit_status = Some(ExitStatus::from_raw(raw));
+                            }
+                            "cursor" => list.list.cursor_y = number()?,
+                            "scroll_x" => list.list.scroll_x = number()?,
+                            "scroll_y" => list.list.scroll_y = number()?,
+                            "stdout" | "stderr" | "line" => {
+                                let stream = match key {
+                                    "stdout" => Some(Stream::Stdout),
+                                    "stderr" => Some(Stream::Stderr),
+                                    _ => None,
+                                };
+                                let text = unescape(value).map_err(fail)?;
+                                list.push(OutputLine::new(text, Vec::new(), stream));
+                            }
+                            key => return Err(fail(format!("Unknown key `{}`", key))),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // NOTE: the session file could be edited by hand, so the positions
+    // are not trusted
+    for tab in tabs.iter_mut() {
+        tab.current = usize::min(tab.current, tab.lists.len().saturating_sub(1));
+        tab.history.cursor_y = tab.current;
+        tab.exit_status = tab.lists.last().and_then(|list| list.exit_status);
+        for list in tab.lists.iter_mut() {
+            if list.list.cursor_y >= list.list.items.len() {
+                list.list.cursor_y = list.list.items.len().saturating_sub(1);
+            }
+        }
+    }
+    if current_tab >= tabs.len() {
+        current_tab = 0;
+    }
+
+    Ok((tabs, current_tab))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        for text in ["", " a\\b\tc\r\n", "  ", "foo = bar"].iter() {
+            assert_eq!(unescape(&escape(text)).unwrap(), *text);
+            let line = format!("line = {}", escape(text));
+            let (_, value) = config::split_key_value(&line).unwrap();
+            assert_eq!(unescape(value).unwrap(), *text);
+        }
+        assert!(unescape("\\q").is_err());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut output_buffer = OutputBuffer::new(shell::Shell::default());
+        output_buffer.tab_mut().user_provided_cmdline = Some("make".to_string());
+        let mut list = OutputList::new();
+        list.cmdline = Some("make".to_string());
+        list.pid = Some(42);
+        list.exit_status = Some(ExitStatus::from_raw(2 << 8));
+        list.push(OutputLine::from("PID: 42, Command: make\n".to_string()));
+        list.push(OutputLine::new(
+            "main.c:1:1: error\n".to_string(),
+            Vec::new(),
+            Some(Stream::Stderr),
+        ));
+        list.list.cursor_y = 1;
+        output_buffer.tab_mut().push_list(list);
+        output_buffer.new_tab(None);
+        output_buffer.tab_mut().name = Some("empty".to_string());
+
+        let mut session = Vec::new();
+        save(&output_buffer, &mut session).unwrap();
+        let (tabs, current_tab) = load(&String::from_utf8(session).unwrap()).unwrap();
+
+        assert_eq!(current_tab, 1);
+        assert_eq!(tabs.len(), 2);
+        assert_eq!(tabs[0].user_provided_cmdline.as_deref(), Some("make"));
+        assert_eq!(tabs[0].status(), "2");
+        assert_eq!(tabs[1].title(), "empty");
+
+        let list = tabs[0].current_list().unwrap();
+        assert_eq!(list.pid, Some(42));
+        assert_eq!(list.list.cursor_y, 1);
+        assert_eq!(list.lines[1].text, "main.c:1:1: error\n");
+        assert!(list.lines[1].stream == Some(Stream::Stderr));
+    }
+}
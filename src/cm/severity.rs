@@ -0,0 +1,142 @@
+use super::*;
+use std::fmt;
+use std::str::FromStr;
+
+/// The name of the capture group the severity of a match is taken from.
+/// See [Severity::classify](enum.Severity.html#method.classify)
+pub const SEVERITY_GROUP: &str = "severity";
+
+/// How bad the thing a match reports is
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    /// Interprets the text captured by the [SEVERITY_GROUP](constant.SEVERITY_GROUP.html)
+    /// the way the tools spell it: `error`, `fatal error`, `E`, `Warning`,
+    /// `note`, `help`, etc.
+    pub fn classify(text: &str) -> Option<Self> {
+        let text = text.trim().to_lowercase();
+        match text.as_str() {
+            "e" | "err" | "error" | "fatal" | "fatal error" | "failed" | "failure" => {
+                Some(Severity::Error)
+            }
+            "w" | "warn" | "warning" => Some(Severity::Warning),
+            "i" | "info" | "information" | "note" | "help" | "hint" => Some(Severity::Info),
+            _ => None,
+        }
+    }
+
+    pub fn pair(self) -> i16 {
+        match self {
+            Severity::Error => ERROR_PAIR,
+            Severity::Warning => WARNING_PAIR,
+            Severity::Info => INFO_PAIR,
+        }
+    }
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Severity::Error),
+            "warning" => Ok(Severity::Warning),
+            "info" => Ok(Severity::Info),
+            _ => Err(format!("Unknown severity `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The amount of the records of each severity in the output, like the
+/// mode line of Emacs' compilation-mode shows them
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct SeverityCounts {
+    pub errors: usize,
+    pub warnings: usize,
+    pub infos: usize,
+}
+
+impl SeverityCounts {
+    pub fn add(&mut self, severity: Option<Severity>) {
+        match severity {
+            Some(Severity::Error) => self.errors += 1,
+            Some(Severity::Warning) => self.warnings += 1,
+            Some(Severity::Info) => self.infos += 1,
+            None => {}
+        }
+    }
+
+    pub fn merge(&mut self, other: Self) {
+        self.errors += other.errors;
+        self.warnings += other.warnings;
+        self.infos += other.infos;
+    }
+}
+
+impl fmt::Display for SeverityCounts {
+    /// `3 errors, 12 warnings`. The severities that were not seen at all
+    /// are omitted.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let plural = |count: usize, name: &str| {
+            format!("{} {}{}", count, name, if count == 1 { "" } else { "s" })
+        };
+        let parts: Vec<String> = [
+            (self.errors, "error"),
+            (self.warnings, "warning"),
+            (self.infos, "info"),
+        ]
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, name)| plural(*count, name))
+        .collect();
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(Severity::classify("Error"), Some(Severity::Error));
+        assert_eq!(Severity::classify("fatal error"), Some(Severity::Error));
+        assert_eq!(Severity::classify("W"), Some(Severity::Warning));
+        assert_eq!(Severity::classify("note"), Some(Severity::Info));
+        assert_eq!(Severity::classify("oops"), None);
+    }
+
+    #[test]
+    fn test_counts() {
+        let mut counts = SeverityCounts::default();
+        assert_eq!(counts.to_string(), "");
+        for severity in [
+            Some(Severity::Error),
+            Some(Severity::Warning),
+            None,
+            Some(Severity::Warning),
+            Some(Severity::Error),
+            Some(Severity::Error),
+            Some(Severity::Info),
+        ] {
+            counts.add(severity);
+        }
+        assert_eq!(counts.to_string(), "3 errors, 2 warnings, 1 info");
+    }
+}
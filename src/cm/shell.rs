@@ -0,0 +1,280 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+/// The command lines that start with ARGV_PREFIX are split into words
+/// and executed directly, bypassing the shell. See [split](fn.split.html)
+/// for the syntax of the words.
+pub const ARGV_PREFIX: &str = "argv:";
+
+/// The shell the command lines are executed with: `<path> <args>... <cmdline>`
+#[derive(Clone)]
+pub struct Shell {
+    pub path: PathBuf,
+    /// `-c` for most of the shells, `-lc` for a login bash, etc.
+    pub args: Vec<String>,
+}
+
+/// `/bin/sh -c`
+impl Default for Shell {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("/bin/sh"),
+            args: vec!["-c".to_string()],
+        }
+    }
+}
+
+/// The quoting rules of the shells differ
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Dialect {
+    /// sh, bash, zsh, dash, ksh and friends
+    Posix,
+    Fish,
+    Nu,
+}
+
+impl Dialect {
+    /// Quotes the `text` so the shell of the dialect treats it as a
+    /// single word with no expansions.
+    pub fn quote(self, text: &str) -> String {
+        let is_safe = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+        if !text.is_empty() && text.chars().all(is_safe) {
+            return text.to_string();
+        }
+
+        match self {
+            Dialect::Posix => format!("'{}'", text.replace('\'', r"'\''")),
+            // NOTE: within single quotes fish only understands \' and \\
+            Dialect::Fish => format!("'{}'", text.replace('\\', r"\\").replace('\'', r"\'")),
+            // NOTE: the raw strings of nu can not be terminated early by
+            // the `text` if they have more `#` than any `'#...` in it
+            Dialect::Nu => {
+                let mut hashes = "#".to_string();
+                while text.contains(&format!("'{}", hashes)) {
+                    hashes.push('#');
+                }
+                format!("r{}'{}'{}", hashes, text, hashes)
+            }
+        }
+    }
+
+    /// Quotes the `bytes` like [Dialect::quote](enum.Dialect.html#method.quote).
+    /// The invalid UTF-8 sequences are spelled with the escapes of the
+    /// shell, so they reach the command as they are. nu has no such
+    /// escapes, so they are replaced with U+FFFD there.
+    pub fn quote_bytes(self, bytes: &[u8]) -> String {
+        if let Ok(text) = std::str::from_utf8(bytes) {
+            return self.quote(text);
+        }
+        if self == Dialect::Nu {
+            return self.quote(&String::from_utf8_lossy(bytes));
+        }
+
+        let mut result = String::new();
+        for chunk in bytes.utf8_chunks() {
+            if !chunk.valid().is_empty() {
+                result.push_str(&self.quote(chunk.valid()));
+            }
+            for byte in chunk.invalid() {
+                match self {
+                    Dialect::Posix => result.push_str(&format!("\"$(printf '\\{:03o}')\"", byte)),
+                    _ => result.push_str(&format!("\\X{:02X}", byte)),
+                }
+            }
+        }
+        result
+    }
+}
+
+impl Shell {
+    /// Figures out the quoting rules from the name of the shell
+    pub fn dialect(&self) -> Dialect {
+        match self.path.file_name().and_then(|name| name.to_str()) {
+            Some("fish") => Dialect::Fish,
+            Some("nu") => Dialect::Nu,
+            _ => Dialect::Posix,
+        }
+    }
+
+    /// Builds the command that runs the `cmdline`. The command lines
+    /// starting with [ARGV_PREFIX](constant.ARGV_PREFIX.html) do not
+    /// involve the shell at all.
+    pub fn command(&self, cmdline: &str) -> Result<Command, String> {
+        match parse_argv(cmdline) {
+            Some(argv) => {
+                let argv = argv?;
+                let (program, args) = argv
+                    .split_first()
+                    .ok_or_else(|| format!("`{}` has no program to run", cmdline))?;
+                let mut command = Command::new(program);
+                command.args(args);
+                Ok(command)
+            }
+            None => {
+                let mut command = Command::new(&self.path);
+                command.args(&self.args);
+                command.arg(cmdline);
+                Ok(command)
+            }
+        }
+    }
+}
+
+/// Quotes the `word` for [split](fn.split.html)
+pub fn quote_word(word: &str) -> String {
+    let is_safe = |c: char| !c.is_whitespace() && c != '\'' && c != '"';
+    if !word.is_empty() && word.chars().all(is_safe) {
+        word.to_string()
+    } else if !word.contains('\'') {
+        format!("'{}'", word)
+    } else {
+        format!("\"{}\"", word.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+/// Turns the pre-split `argv` into a command line that is executed
+/// without the shell
+pub fn join_argv(argv: &[String]) -> String {
+    let words = argv
+        .iter()
+        .map(|word| quote_word(word))
+        .collect::<Vec<String>>()
+        .join(" ");
+    format!("{} {}", ARGV_PREFIX, words)
+}
+
+/// Splits the `words` separated by whitespace. The words may be grouped
+/// with quotes: everything within `'...'` is taken literally, within
+/// `"..."` only `\"` and `\\` are escapes. Outside of the quotes the
+/// backslash is not special, so the `\1` substitutions of the command
+/// templates do not need any escaping.
+pub fn split(words: &str) -> Result<Vec<String>, String> {
+    let mut result = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = words.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if let Some(word) = word.take() {
+                    result.push(word);
+                }
+            }
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("Unterminated single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') if chars.peek() == Some(&'"') || chars.peek() == Some(&'\\') => {
+                            word.extend(chars.next())
+                        }
+                        Some(c) => word.push(c),
+                        None => return Err("Unterminated double quote".to_string()),
+                    }
+                }
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    result.extend(word);
+    Ok(result)
+}
+
+/// Returns the words of the `cmdline` if it starts with
+/// [ARGV_PREFIX](constant.ARGV_PREFIX.html), `None` if it is a regular
+/// shell command line.
+pub fn parse_argv(cmdline: &str) -> Option<Result<Vec<String>, String>> {
+    cmdline.trim_start().strip_prefix(ARGV_PREFIX).map(split)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        assert_eq!(
+            split(r#"vim +\2  '\1' "it's \"here\"" a'b c'd"#).unwrap(),
+            vec!["vim", r"+\2", r"\1", "it's \"here\"", "ab cd"]
+        );
+        assert_eq!(split("''").unwrap(), vec![""]);
+        assert!(split("'oops").is_err());
+
+        let argv = vec![
+            "grep".to_string(),
+            "it's".to_string(),
+            "a b".to_string(),
+            r#"\"q"#.to_string(),
+            r#"it's "x""#.to_string(),
+        ];
+        assert_eq!(parse_argv(&join_argv(&argv)), Some(Ok(argv)));
+        assert_eq!(parse_argv("make -j4"), None);
+    }
+
+    const HOSTILE_NAMES: &[&str] = &[
+        "a b.rs",
+        "it's.rs",
+        "$(touch pwned).rs",
+        "`id`.rs",
+        ";ls",
+        "a\\nb|c&d>e",
+        "\\'\"*?.rs",
+        "-rf",
+        "",
+    ];
+
+    #[test]
+    fn test_quote_posix() {
+        assert_eq!(Dialect::Posix.quote("src/main.rs"), "src/main.rs");
+        assert_eq!(Dialect::Posix.quote("it's"), r"'it'\''s'");
+        for name in HOSTILE_NAMES {
+            let output = Shell::default()
+                .command(&format!("printf %s {}", Dialect::Posix.quote(name)))
+                .unwrap()
+                .output()
+                .unwrap();
+            assert_eq!(String::from_utf8(output.stdout).unwrap(), *name);
+        }
+    }
+
+    #[test]
+    fn test_quote_bytes() {
+        assert_eq!(Dialect::Posix.quote_bytes(b"it's"), r"'it'\''s'");
+        assert_eq!(Dialect::Fish.quote_bytes(b"caf\xe9 .c"), r"caf\XE9' .c'");
+        assert_eq!(Dialect::Nu.quote_bytes(b"caf\xe9.c"), "r#'caf\u{FFFD}.c'#");
+        for name in [&b"caf\xe9.c"[..], b"\xff'\xfe x", b"%s\\\xe2\x82"] {
+            let output = Shell::default()
+                .command(&format!("printf %s {}", Dialect::Posix.quote_bytes(name)))
+                .unwrap()
+                .output()
+                .unwrap();
+            assert_eq!(output.stdout, name);
+        }
+    }
+
+    #[test]
+    fn test_quote_fish_and_nu() {
+        assert_eq!(Dialect::Fish.quote(r"it's \n"), r"'it\'s \\n'");
+        assert_eq!(Dialect::Nu.quote("$(x)"), "r#'$(x)'#");
+        assert_eq!(Dialect::Nu.quote("a'#b"), "r##'a'#b'##");
+
+        let shell = |path: &str| Shell {
+            path: PathBuf::from(path),
+            args: vec!["-c".to_string()],
+        };
+        assert_eq!(shell("/usr/bin/fish").dialect(), Dialect::Fish);
+        assert_eq!(shell("nu").dialect(), Dialect::Nu);
+        assert_eq!(shell("/bin/bash").dialect(), Dialect::Posix);
+    }
+}
@@ -25,6 +25,18 @@ impl StringList {
         self.list.current_item()
     }
 
+    /// Moves the cursor to the `item`, appending it to the end of the
+    /// list first if it's not there yet.
+    pub fn select_or_push(&mut self, item: &str) {
+        match self.list.items.iter().position(|x| x == item) {
+            Some(index) => self.list.cursor_y = index,
+            None => {
+                self.list.items.push(item.to_string());
+                self.list.cursor_y = self.list.items.len() - 1;
+            }
+        }
+    }
+
     pub fn render(&mut self, rect: Rect, focused: bool, cursor: &mut Cursor) {
         self.list.render(rect, focused);
         if let StringListState::Editing { .. } = self.state {
@@ -1,3 +1,4 @@
+use super::*;
 use ncurses::*;
 
 pub const REGULAR_PAIR: i16 = 1;
@@ -7,6 +8,47 @@ pub const MATCH_PAIR: i16 = 4;
 pub const MATCH_CURSOR_PAIR: i16 = 5;
 pub const UNFOCUSED_MATCH_CURSOR_PAIR: i16 = 6;
 pub const STATUS_ERROR_PAIR: i16 = 7;
+pub const STDERR_PAIR: i16 = 8;
+pub const ERROR_PAIR: i16 = 9;
+pub const WARNING_PAIR: i16 = 10;
+pub const INFO_PAIR: i16 = 11;
+/// The pairs for the colors of the child output (See ansi::Style) are
+/// allocated starting from ANSI_PAIRS_BEGIN. One pair per each combination
+/// of 8 ANSI colors + default for foreground and background.
+pub const ANSI_PAIRS_BEGIN: i16 = 16;
+const ANSI_COLORS_COUNT: i16 = 9;
+const ANSI_DEFAULT_FG: i16 = COLOR_WHITE;
+const ANSI_DEFAULT_BG: i16 = COLOR_BLACK;
+
+fn ansi_pair(fg: Option<i16>, bg: Option<i16>) -> i16 {
+    let index = |color: Option<i16>| color.unwrap_or(ANSI_COLORS_COUNT - 1);
+    let pair = ANSI_PAIRS_BEGIN + index(fg) * ANSI_COLORS_COUNT + index(bg);
+    if (pair as i32) < COLOR_PAIRS() {
+        pair
+    } else {
+        REGULAR_PAIR
+    }
+}
+
+pub fn ansi_attr(style: ansi::Style) -> attr_t {
+    let (fg, bg) = if style.reverse {
+        (
+            Some(style.bg.unwrap_or(ANSI_DEFAULT_BG)),
+            Some(style.fg.unwrap_or(ANSI_DEFAULT_FG)),
+        )
+    } else {
+        (style.fg, style.bg)
+    };
+
+    let mut attr = COLOR_PAIR(ansi_pair(fg, bg));
+    if style.bold {
+        attr |= A_BOLD();
+    }
+    if style.underline {
+        attr |= A_UNDERLINE();
+    }
+    attr
+}
 
 pub fn init_style() {
     start_color();
@@ -17,4 +59,29 @@ pub fn init_style() {
     init_pair(MATCH_CURSOR_PAIR, COLOR_RED, COLOR_WHITE);
     init_pair(UNFOCUSED_MATCH_CURSOR_PAIR, COLOR_BLACK, COLOR_CYAN);
     init_pair(STATUS_ERROR_PAIR, COLOR_RED, COLOR_BLACK);
+    init_pair(STDERR_PAIR, COLOR_RED, COLOR_BLACK);
+    init_pair(ERROR_PAIR, COLOR_RED, COLOR_BLACK);
+    init_pair(WARNING_PAIR, COLOR_MAGENTA, COLOR_BLACK);
+    init_pair(INFO_PAIR, COLOR_CYAN, COLOR_BLACK);
+
+    for fg in 0..ANSI_COLORS_COUNT {
+        for bg in 0..ANSI_COLORS_COUNT {
+            let pair = ANSI_PAIRS_BEGIN + fg * ANSI_COLORS_COUNT + bg;
+            if (pair as i32) < COLOR_PAIRS() {
+                init_pair(
+                    pair,
+                    if fg < ANSI_COLORS_COUNT - 1 {
+                        fg
+                    } else {
+                        ANSI_DEFAULT_FG
+                    },
+                    if bg < ANSI_COLORS_COUNT - 1 {
+                        bg
+                    } else {
+                        ANSI_DEFAULT_BG
+                    },
+                );
+            }
+        }
+    }
 }
@@ -0,0 +1,508 @@
+use super::*;
+use ncurses::*;
+use os_pipe::pipe;
+use std::fs::File;
+use std::io;
+use std::os::unix::io::{FromRawFd, IntoRawFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, ExitStatus, Stdio};
+
+/// `matches` tells whether a match starts at the line at the index
+/// within the lines. The lines around it matter for the multi-line
+/// records (See [is_multiline](fn.is_multiline.html)).
+fn is_current_line_matches<F: Fn(&[OutputLine], usize) -> bool>(
+    list: &ItemList<OutputLine>,
+    matches: &F,
+) -> bool {
+    list.cursor_y < list.items.len() && matches(&list.items, list.cursor_y)
+}
+
+/// A single tab of the [OutputBuffer](struct.OutputBuffer.html). Every
+/// tab runs its own child independently from the other tabs.
+pub struct Tab {
+    /// the name of the tab set by the user. See [Tab::title](struct.Tab.html#method.title)
+    pub name: Option<String>,
+    /// the command line provided by the user for this tab (as opposed to
+    /// the ones run with `run_into_itself`). Rerun by `rerun`.
+    pub user_provided_cmdline: Option<String>,
+    /// the history of all of the runs within the Tab. Entries are
+    /// removed only explicitly by [Tab::prune](struct.Tab.html#method.prune)
+    pub lists: Vec<OutputList>,
+    /// the index of the list in [Tab::lists](struct.Tab.html#structfield.lists)
+    /// that is currently shown
+    pub current: usize,
+    /// the index of the list the currently running child writes into
+    pub child_list: usize,
+    /// the history pane of the Tab. The items are regenerated from
+    /// [Tab::lists](struct.Tab.html#structfield.lists) on every render.
+    pub history: ItemList<String>,
+    /// currently running process that generates data for the Tab.
+    /// See [Tab::poll_cmdline_output](struct.Tab.html#method.poll_cmdline_output)
+    pub child: Option<(Vec<ChildStream>, Child)>,
+    /// the exit status of the last child of the Tab. `None` if the child
+    /// is still running or nothing was run in the Tab yet.
+    pub exit_status: Option<ExitStatus>,
+    /// master sides of the pseudo-terminals the currently running child
+    /// is attached to. Empty if the child was spawned on plain pipes.
+    /// See [Tab::run_cmdline](struct.Tab.html#method.run_cmdline)
+    pub pty_masters: Vec<File>,
+    /// the output that is not produced by a child of the Tab, like the
+    /// stdin of cm. See [Tab::open_input](struct.Tab.html#method.open_input)
+    pub input: Option<ChildStream>,
+    /// the index of the list the [Tab::input](struct.Tab.html#structfield.input)
+    /// is read into
+    pub input_list: usize,
+}
+
+impl Tab {
+    pub fn new(user_provided_cmdline: Option<String>) -> Self {
+        Self {
+            name: None,
+            user_provided_cmdline,
+            lists: Vec::new(),
+            current: 0,
+            child_list: 0,
+            history: ItemList::new(),
+            child: None,
+            exit_status: None,
+            pty_masters: Vec::new(),
+            input: None,
+            input_list: 0,
+        }
+    }
+
+    pub fn title(&self) -> String {
+        self.name
+            .clone()
+            .or_else(|| self.user_provided_cmdline.clone())
+            .unwrap_or_else(|| "cm".to_string())
+    }
+
+    pub fn status(&self) -> String {
+        if self.input.is_some() && self.child.is_none() {
+            return "reading".to_string();
+        }
+        describe_exit_status(self.child.is_some(), self.exit_status)
+    }
+
+    pub fn current_list(&self) -> Option<&OutputList> {
+        self.lists.get(self.current)
+    }
+
+    pub fn current_list_mut(&mut self) -> Option<&mut OutputList> {
+        self.lists.get_mut(self.current)
+    }
+
+    /// Appends a new list to the end of the history and shows it
+    pub fn push_list(&mut self, list: OutputList) {
+        self.lists.push(list);
+        self.current = self.lists.len() - 1;
+        self.history.cursor_y = self.current;
+    }
+
+    pub fn push(&mut self, line: String) {
+        if let Some(list) = self.current_list_mut() {
+            list.push(OutputLine::from(line));
+        }
+    }
+
+    /// Shows the previous entry of the history without removing the
+    /// current one.
+    pub fn back(&mut self) {
+        if self.current > 0 {
+            self.current -= 1;
+            self.history.cursor_y = self.current;
+        }
+    }
+
+    /// Shows the next entry of the history. The opposite of
+    /// [Tab::back](struct.Tab.html#method.back)
+    pub fn forward(&mut self) {
+        if self.current + 1 < self.lists.len() {
+            self.current += 1;
+            self.history.cursor_y = self.current;
+        }
+    }
+
+    pub fn open(&mut self, index: usize) {
+        if index < self.lists.len() {
+            self.current = index;
+            self.history.cursor_y = self.current;
+        }
+    }
+
+    /// Removes the `index` entry from the history. The output of the
+    /// currently running child can not be removed.
+    pub fn prune(&mut self, index: usize) -> Result<(), String> {
+        if index >= self.lists.len() {
+            return Ok(());
+        }
+
+        if self.child.is_some() {
+            if index == self.child_list {
+                return Err("Cannot prune the output of a running command".to_string());
+            } else if index < self.child_list {
+                self.child_list -= 1;
+            }
+        }
+
+        if self.input.is_some() {
+            if index == self.input_list {
+                return Err("Cannot prune the output that is still being read".to_string());
+            } else if index < self.input_list {
+                self.input_list -= 1;
+            }
+        }
+
+        self.lists.remove(index);
+        if index < self.current || self.current >= self.lists.len() {
+            self.current = self.current.saturating_sub(1);
+        }
+        self.history.cursor_y = usize::min(index, self.lists.len().saturating_sub(1));
+        Ok(())
+    }
+
+    pub fn refresh_history(&mut self) {
+        let running = self.child.as_ref().map(|_| self.child_list);
+        self.history.items = self
+            .lists
+            .iter()
+            .enumerate()
+            .map(|(index, list)| {
+                format!(
+                    "{} {}",
+                    if index == self.current { "*" } else { " " },
+                    list.summary(running == Some(index))
+                )
+            })
+            .collect();
+    }
+
+    pub fn jump_to_next_match<F: Fn(&[OutputLine], usize) -> bool>(&mut self, matches: F) {
+        if let Some(OutputList { list, .. }) = self.current_list_mut() {
+            list.down();
+            while !is_current_line_matches(list, &matches) && !list.is_at_end() {
+                list.down();
+            }
+        }
+    }
+
+    pub fn jump_to_prev_match<F: Fn(&[OutputLine], usize) -> bool>(&mut self, matches: F) {
+        if let Some(OutputList { list, .. }) = self.current_list_mut() {
+            list.up();
+            while !is_current_line_matches(list, &matches) && !list.is_at_begin() {
+                list.up();
+            }
+        }
+    }
+
+    pub fn ctrlc(&mut self) {
+        if cfg!(unix) {
+            if let Some((_, child)) = &self.child {
+                unsafe {
+                    // NOTE: taken from https://github.com/watchexec/watchexec/commit/f3c6df8845ed13e231cd9cbd88afdebe5fd97569
+                    // grep for @pgid
+                    libc::killpg(child.id() as i32, libc::SIGINT);
+                }
+            }
+        }
+    }
+
+    pub fn kill_the_child(&mut self) {
+        if let Some((_, child)) = &mut self.child {
+            unsafe {
+                // NOTE: taken from https://github.com/watchexec/watchexec/commit/f3c6df8845ed13e231cd9cbd88afdebe5fd97569
+                // grep for @pgid
+                libc::killpg(child.id() as i32, libc::SIGTERM);
+            }
+            let status = child
+                .wait()
+                .expect("Error waiting for currently running child process");
+            self.exit_status = Some(status);
+            if let Some(list) = self.lists.get_mut(self.child_list) {
+                list.exit_status = Some(status);
+            }
+            self.child = None;
+            self.pty_masters.clear();
+        }
+    }
+
+    /// Spawns `cmdline` in the `shell` as the new child of the
+    /// Tab. The stdout and stderr of the child are collected
+    /// separately. If `pty` is `true` each of them is attached to its
+    /// own pseudo-terminal instead of a pipe, so the child behaves the
+    /// same way it would behave in a regular terminal.
+    pub fn run_cmdline(
+        &mut self,
+        cmdline: String,
+        shell: &shell::Shell,
+        pty: bool,
+        (w, h): (usize, usize),
+    ) -> Result<(), String> {
+        let mut command = shell.command(&cmdline)?;
+
+        // NOTE: the pseudo-terminals and the pipes are opened before the
+        // running child is killed, so it keeps running if they can not
+        // be opened (e.g. when the limit of the pseudo-terminals is
+        // reached)
+        let error =
+            |err: io::Error| format!("Could not collect the output of `{}`: {}", cmdline, err);
+        let (stdout, stderr, masters) = if pty {
+            let (stdout_master, stdout_slave) = pty::open(w, h).map_err(error)?;
+            let (stderr_master, stderr_slave) = pty::open(w, h).map_err(error)?;
+            let masters = vec![
+                stdout_master.try_clone().map_err(error)?,
+                stderr_master.try_clone().map_err(error)?,
+            ];
+            command.stdin(Stdio::from(stdout_slave.try_clone().map_err(error)?));
+            command.stdout(Stdio::from(stdout_slave));
+            command.stderr(Stdio::from(stderr_slave));
+            // NOTE: setsid(2) puts the child into a new process group
+            // as well, so killpg still works on it. Grep for @pgid
+            unsafe {
+                command.pre_exec(pty::make_controlling_terminal);
+            }
+            (stdout_master, stderr_master, masters)
+        } else {
+            let (stdout_reader, stdout_writer) = pipe().map_err(error)?;
+            let (stderr_reader, stderr_writer) = pipe().map_err(error)?;
+            // NOTE: the child must not steal the input piped into cm
+            // itself (e.g. `make | cm -`)
+            command.stdin(Stdio::null());
+            command.stdout(stdout_writer);
+            command.stderr(stderr_writer);
+            // NOTE: taken from https://github.com/watchexec/watchexec/commit/f3c6df8845ed13e231cd9cbd88afdebe5fd97569
+            // grep for @pgid
+            unsafe {
+                command.pre_exec(|| {
+                    libc::setpgid(0, 0);
+                    Ok(())
+                });
+            }
+            unsafe {
+                (
+                    File::from_raw_fd(stdout_reader.into_raw_fd()),
+                    File::from_raw_fd(stderr_reader.into_raw_fd()),
+                    Vec::new(),
+                )
+            }
+        };
+
+        // TODO(#102): cm does not warn the user when it kills the child process
+        self.kill_the_child();
+        self.pty_masters = masters;
+
+        let spawned = command.spawn();
+        // NOTE: the slaves of the pseudo-terminals and the writing ends
+        // of the pipes must be closed on our side even if the spawning
+        // failed
+        drop(command);
+        let child = spawned.map_err(|err| {
+            self.pty_masters.clear();
+            format!("Could not run `{}`: {}", cmdline, err)
+        })?;
+
+        let mut new_list = OutputList::new();
+        if let Some(prev_list) = self.current_list() {
+            new_list.filter = prev_list.filter;
+        }
+        new_list.push(OutputLine::from(format!(
+            "PID: {}, Command: {}",
+            child.id(),
+            cmdline.as_str()
+        )));
+        new_list.pid = Some(child.id());
+        new_list.cmdline = Some(cmdline);
+        self.push_list(new_list);
+        self.child_list = self.current;
+        self.exit_status = None;
+
+        self.child = Some((
+            vec![
+                ChildStream::new(Stream::Stdout, stdout, TABSIZE() as usize),
+                ChildStream::new(Stream::Stderr, stderr, TABSIZE() as usize),
+            ],
+            child,
+        ));
+
+        Ok(())
+    }
+
+    /// Reads the `file` into a new list as if it was the output of a
+    /// child. The `source` describes where the output comes from for the
+    /// user. The `file` is read lazily, a limited amount of lines per
+    /// [Tab::poll_cmdline_output](struct.Tab.html#method.poll_cmdline_output).
+    /// If `follow` is `true` the reading does not stop at the end of the
+    /// `file`.
+    pub fn open_input(&mut self, source: &str, file: File, follow: bool) {
+        let mut new_list = OutputList::new();
+        new_list.cmdline = Some(format!("<{}>", source));
+        new_list.push(OutputLine::from(format!("Reading from {}", source)));
+        self.push_list(new_list);
+        self.input_list = self.current;
+        let mut input = ChildStream::new(Stream::Stdout, file, TABSIZE() as usize);
+        input.follow = follow;
+        self.input = Some(input);
+    }
+
+    /// Polls changes from the currently running child (see
+    /// [Tab::run_cmdline](struct.Tab.html#method.run_cmdline),
+    /// [Tab::child](struct.Tab.html#structfield.child)).
+    ///
+    /// Returns `true` if new input was received, `false` when nothing
+    /// was received.
+    pub fn poll_cmdline_output(&mut self) -> bool {
+        let mut changed = false;
+
+        if let Some(input) = &mut self.input {
+            const INPUT_BUFFER_LIMIT: usize = 1024;
+            if let Some(list) = self.lists.get_mut(self.input_list) {
+                if input.follow && input.rewind_if_truncated() {
+                    list.push(OutputLine::from("-- File Truncated --".to_string()));
+                    changed = true;
+                }
+                changed = input.poll(list, INPUT_BUFFER_LIMIT) || changed;
+                if input.eof {
+                    list.push(OutputLine::from("-- End of Input --".to_string()));
+                    changed = true;
+                }
+            }
+            if input.eof {
+                self.input = None;
+            }
+        }
+
+        if let Some((streams, child)) = &mut self.child {
+            const FLUSH_BUFFER_LIMIT: usize = 1024;
+            if let Some(list) = self.lists.get_mut(self.child_list) {
+                for stream in streams.iter_mut() {
+                    changed = stream.poll(list, FLUSH_BUFFER_LIMIT) || changed;
+                }
+            }
+
+            if let Some(status) = child
+                .try_wait()
+                .expect("Error attempting to wait for child output")
+            {
+                // NOTE: the child is not going to finish its last line anymore
+                if let Some(list) = self.lists.get_mut(self.child_list) {
+                    for stream in streams.iter_mut() {
+                        if !stream.pending.is_empty() {
+                            stream.push_pending(list);
+                            changed = true;
+                        }
+                    }
+                }
+
+                self.exit_status = Some(status);
+                if let Some(list) = self.lists.get_mut(self.child_list) {
+                    list.exit_status = Some(status);
+                }
+                match status.code() {
+                    Some(code) => {
+                        if let Some(list) = self.lists.get_mut(self.child_list) {
+                            list.push(OutputLine::from(format!(
+                                "-- Execution Finished with status code: {} --",
+                                code
+                            )));
+                            changed = true;
+                        }
+                    }
+                    None => {
+                        if let Some(list) = self.lists.get_mut(self.child_list) {
+                            list.push(OutputLine::from(
+                                "-- Execution Terminated by a signal --".to_string(),
+                            ));
+                            changed = true;
+                        }
+                    }
+                }
+                self.child = None;
+                self.pty_masters.clear();
+            }
+        }
+
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_navigation_and_pruning() {
+        let mut tab = Tab::new(None);
+        for cmdline in ["a", "b", "c"].iter() {
+            let mut list = OutputList::new();
+            list.cmdline = Some(cmdline.to_string());
+            tab.push_list(list);
+        }
+        assert_eq!(tab.current, 2);
+
+        tab.back();
+        tab.back();
+        tab.back();
+        assert_eq!(tab.current, 0);
+        tab.forward();
+        assert_eq!(tab.current, 1);
+        assert_eq!(tab.lists.len(), 3);
+
+        tab.prune(0).unwrap();
+        assert_eq!(tab.current, 0);
+        assert_eq!(tab.current_list().unwrap().cmdline.as_deref(), Some("b"));
+
+        tab.forward();
+        tab.prune(1).unwrap();
+        assert_eq!(tab.current, 0);
+        assert_eq!(tab.lists.len(), 1);
+    }
+
+    #[test]
+    fn test_run_cmdline_under_piped_stdin() {
+        use std::env::{current_exe, var_os};
+        use std::io::Write;
+        use std::thread::sleep;
+        use std::time::{Duration, Instant};
+
+        const CHILD: &str = "CM_TEST_PIPED_STDIN";
+
+        // NOTE: the test runs itself in a child process with the input
+        // piped, so the stdin of the tests running in parallel is left
+        // alone
+        if var_os(CHILD).is_none() {
+            let name = format!(
+                "{}::test_run_cmdline_under_piped_stdin",
+                module_path!().split_once("::").unwrap().1
+            );
+            let mut child = std::process::Command::new(current_exe().unwrap())
+                .args(["--exact", &name, "--quiet"])
+                .env(CHILD, "1")
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .spawn()
+                .unwrap();
+            let mut stdin = child.stdin.take().unwrap();
+            stdin.write_all(b"input of cm\n").unwrap();
+            drop(stdin);
+            assert!(child.wait().unwrap().success());
+            return;
+        }
+
+        // NOTE: the child fails if it manages to read the input of cm
+        let mut tab = Tab::new(None);
+        let result = tab.run_cmdline(
+            "sh -c 'if read line; then exit 1; fi'".to_string(),
+            &shell::Shell::default(),
+            false,
+            (80, 24),
+        );
+        let started = Instant::now();
+        while tab.child.is_some() && started.elapsed() < Duration::from_secs(5) {
+            tab.poll_cmdline_output();
+            sleep(Duration::from_millis(10));
+        }
+        assert_eq!(result, Ok(()));
+        assert_eq!(tab.exit_status.and_then(|status| status.code()), Some(0));
+    }
+}
@@ -0,0 +1,400 @@
+use super::*;
+use pcre2::bytes::Regex;
+use std::env;
+use std::ffi::OsStr;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::Path;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Transform {
+    Basename,
+    Dirname,
+    Abspath,
+}
+
+impl Transform {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "basename" => Some(Transform::Basename),
+            "dirname" => Some(Transform::Dirname),
+            "abspath" => Some(Transform::Abspath),
+            _ => None,
+        }
+    }
+
+    fn apply(self, value: Vec<u8>) -> Vec<u8> {
+        let path = Path::new(OsStr::from_bytes(&value));
+        match self {
+            Transform::Basename => path
+                .file_name()
+                .map(|name| name.as_bytes().to_vec())
+                .unwrap_or(value),
+            Transform::Dirname => match path.parent() {
+                Some(parent) if parent.as_os_str().is_empty() => b".".to_vec(),
+                Some(parent) => parent.as_os_str().as_bytes().to_vec(),
+                None => value,
+            },
+            Transform::Abspath if path.is_relative() => env::current_dir()
+                .map(|dir| dir.join(path).into_os_string().into_vec())
+                .unwrap_or(value),
+            Transform::Abspath => value,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Placeholder {
+    group: usize,
+    /// do not quote the value for the shell
+    raw: bool,
+    transforms: Vec<Transform>,
+    /// used when the group did not participate in the match or matched
+    /// an empty string
+    default: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Text(String),
+    Placeholder(Placeholder),
+}
+
+enum Kind {
+    Shell(Vec<Segment>),
+    /// the words of an argv command line (see [shell::ARGV_PREFIX](../shell/constant.ARGV_PREFIX.html))
+    Argv(Vec<Vec<Segment>>),
+}
+
+/// A command template checked against the regex it is going to be
+/// rendered with.
+///
+/// - `\1`, `{1}`, `{file}` are replaced with the numbered or named
+///   capture, `{0}` with the whole match. The value is quoted for the
+///   shell, so a file name like `$(rm -rf ~).c` can not inject anything
+///   into the command.
+/// - `\!1`, `{!file}` insert the capture as is, which is useful when the
+///   capture is already quoted by the template (`vim "\!1"`) or is
+///   supposed to be interpreted by the shell.
+/// - `{col:-1}` falls back to `1` if the group did not match anything.
+/// - `{file|basename}`, `{file|dirname}`, `{file|abspath}` transform the
+///   capture before it is quoted. Transforms can be chained.
+/// - `{{` and `}}` are the literal braces. The braces that do not look
+///   like a placeholder (`awk '{ print $1 }'`, `find -exec {} ;`) as
+///   well as the `${...}` of the shell are left alone.
+///
+/// The words of the argv templates are substituted one by one without
+/// any quoting, so a capture never spills over into the next argument.
+pub struct Template {
+    kind: Kind,
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn resolve_group(name: &str, regex: &Regex, reference: &str) -> Result<usize, String> {
+    let group = if name.chars().all(|c| c.is_ascii_digit()) {
+        name.parse::<usize>().ok()
+    } else {
+        regex
+            .capture_names()
+            .iter()
+            .position(|x| x.as_deref() == Some(name))
+    };
+
+    match group {
+        Some(group) if group < regex.captures_len() => Ok(group),
+        Some(group) => Err(format!(
+            "`{}` refers to the group {}, but the regex has only {}",
+            reference,
+            group,
+            regex.captures_len() - 1
+        )),
+        None => Err(format!(
+            "`{}` refers to an unknown capture group",
+            reference
+        )),
+    }
+}
+
+/// Parses the `{...}` placeholder starting right after the `{` at
+/// `chars[start]`. Returns `None` if it does not look like a placeholder
+/// at all, otherwise the placeholder and the index after the closing `}`.
+fn parse_braces(
+    chars: &[char],
+    start: usize,
+    regex: &Regex,
+) -> Option<Result<(Placeholder, usize), String>> {
+    let mut i = start;
+    let raw = chars.get(i) == Some(&'!');
+    if raw {
+        i += 1;
+    }
+    let name_start = i;
+    while i < chars.len() && is_name_char(chars[i]) {
+        i += 1;
+    }
+    // NOTE: `{name` at the very end of the template is most likely an
+    // unterminated placeholder rather than a literal brace
+    if i == name_start || !matches!(chars.get(i), Some('}') | Some('|') | Some(':') | None) {
+        return None;
+    }
+    let name: String = chars[name_start..i].iter().collect();
+    Some(parse_placeholder(chars, start, i, name, raw, regex))
+}
+
+/// Parses the rest of the `{name...}` placeholder starting at `chars[i]`
+/// right after the `name`
+fn parse_placeholder(
+    chars: &[char],
+    start: usize,
+    mut i: usize,
+    name: String,
+    raw: bool,
+    regex: &Regex,
+) -> Result<(Placeholder, usize), String> {
+    let mut transforms = Vec::new();
+    while chars.get(i) == Some(&'|') {
+        i += 1;
+        let transform_start = i;
+        while i < chars.len() && is_name_char(chars[i]) {
+            i += 1;
+        }
+        let transform: String = chars[transform_start..i].iter().collect();
+        transforms.push(Transform::from_name(&transform).ok_or_else(|| {
+            format!(
+                "Unknown transform `{}` in `{{{}}}`. Expected basename, dirname or abspath",
+                transform, name
+            )
+        })?);
+    }
+
+    let mut default = None;
+    if chars.get(i) == Some(&':') {
+        if chars.get(i + 1) != Some(&'-') {
+            return Err(format!(
+                "Expected `:-` after `{{{}`, like in `{{{}:-default}}`",
+                name, name
+            ));
+        }
+        i += 2;
+        let default_start = i;
+        while i < chars.len() && chars[i] != '}' {
+            i += 1;
+        }
+        default = Some(chars[default_start..i].iter().collect());
+    }
+
+    if chars.get(i) != Some(&'}') {
+        let reference: String = chars[start - 1..].iter().collect();
+        return Err(format!("Unterminated placeholder `{}`", reference));
+    }
+    i += 1;
+
+    let reference: String = chars[start - 1..i].iter().collect();
+    let group = resolve_group(&name, regex, &reference)?;
+    Ok((
+        Placeholder {
+            group,
+            raw,
+            transforms,
+            default,
+        },
+        i,
+    ))
+}
+
+fn parse_segments(template: &str, regex: &Regex) -> Result<Vec<Segment>, String> {
+    let chars: Vec<char> = template.chars().collect();
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let placeholder = match chars[i] {
+            '\\' => {
+                let raw = chars.get(i + 1) == Some(&'!');
+                let digits_start = i + 1 + raw as usize;
+                let mut j = digits_start;
+                while j < chars.len() && chars[j].is_ascii_digit() {
+                    j += 1;
+                }
+                if j == digits_start {
+                    None
+                } else {
+                    // NOTE: all of the digits are taken, so `\10` is the
+                    // group 10 rather than the group 1 followed by `0`
+                    let digits: String = chars[digits_start..j].iter().collect();
+                    let reference: String = chars[i..j].iter().collect();
+                    let placeholder = Placeholder {
+                        group: resolve_group(&digits, regex, &reference)?,
+                        raw,
+                        transforms: Vec::new(),
+                        default: None,
+                    };
+                    Some((placeholder, j))
+                }
+            }
+            '{' if chars.get(i + 1) == Some(&'{') => {
+                text.push('{');
+                i += 2;
+                continue;
+            }
+            '}' if chars.get(i + 1) == Some(&'}') => {
+                text.push('}');
+                i += 2;
+                continue;
+            }
+            // NOTE: `${name}` is the parameter expansion of the shell
+            '{' if i == 0 || chars[i - 1] != '$' => {
+                parse_braces(&chars, i + 1, regex).transpose()?
+            }
+            _ => None,
+        };
+
+        match placeholder {
+            Some((placeholder, next)) => {
+                if !text.is_empty() {
+                    segments.push(Segment::Text(text.split_off(0)));
+                }
+                segments.push(Segment::Placeholder(placeholder));
+                i = next;
+            }
+            None => {
+                text.push(chars[i]);
+                i += 1;
+            }
+        }
+    }
+
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+    Ok(segments)
+}
+
+/// NOTE: the `groups` that are not valid UTF-8 survive only the quoting
+/// of the shell. They are replaced with U+FFFD within the raw placeholders
+/// and the argv words.
+fn render_segments(
+    segments: &[Segment],
+    groups: &[Option<Vec<u8>>],
+    dialect: Option<shell::Dialect>,
+) -> String {
+    let mut result = String::new();
+    for segment in segments {
+        match segment {
+            Segment::Text(text) => result.push_str(text),
+            Segment::Placeholder(placeholder) => {
+                let capture = groups
+                    .get(placeholder.group)
+                    .and_then(Option::as_deref)
+                    .unwrap_or(b"");
+                let mut value = match &placeholder.default {
+                    Some(default) if capture.is_empty() => default.as_bytes().to_vec(),
+                    _ => capture.to_vec(),
+                };
+                for transform in &placeholder.transforms {
+                    value = transform.apply(value);
+                }
+                match dialect {
+                    Some(dialect) if !placeholder.raw => {
+                        result.push_str(&dialect.quote_bytes(&value))
+                    }
+                    _ => result.push_str(&String::from_utf8_lossy(&value)),
+                }
+            }
+        }
+    }
+    result
+}
+
+impl Template {
+    pub fn parse(cmd: &str, regex: &Regex) -> Result<Self, String> {
+        let kind = match shell::parse_argv(cmd) {
+            Some(words) => Kind::Argv(
+                words?
+                    .iter()
+                    .map(|word| parse_segments(word, regex))
+                    .collect::<Result<Vec<_>, String>>()?,
+            ),
+            None => Kind::Shell(parse_segments(cmd, regex)?),
+        };
+        Ok(Self { kind })
+    }
+
+    /// Renders the template with the `groups` (see [capture_groups](../fn.capture_groups.html))
+    /// of the `regex` the template was parsed with.
+    pub fn render(&self, groups: &[Option<Vec<u8>>], shell: &shell::Shell) -> String {
+        match &self.kind {
+            Kind::Shell(segments) => render_segments(segments, groups, Some(shell.dialect())),
+            Kind::Argv(words) => shell::join_argv(
+                &words
+                    .iter()
+                    .map(|segments| render_segments(segments, groups, None))
+                    .collect::<Vec<String>>(),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pcre2::bytes::RegexBuilder;
+
+    fn render(regex: &str, cmd: &str, line: &str) -> Result<String, String> {
+        let regex = RegexBuilder::new().utf(true).build(regex).unwrap();
+        let groups = capture_groups(&regex, line.as_bytes()).unwrap();
+        Template::parse(cmd, &regex)
+            .map(|template| template.render(&groups, &shell::Shell::default()))
+    }
+
+    #[test]
+    fn test_numbered_groups() {
+        let regex = r"(a)(b)(c)(d)(e)(f)(g)(h)(i)(j)";
+        assert_eq!(
+            render(regex, r"echo \1 \10 \1\!2", "abcdefghij").as_deref(),
+            Ok("echo a j ab")
+        );
+        assert!(render(r"(a)", r"echo \10", "a").is_err());
+        assert_eq!(
+            render(r"(a)", r"echo \n {}", "a").as_deref(),
+            Ok(r"echo \n {}")
+        );
+    }
+
+    #[test]
+    fn test_braces() {
+        let regex = r"(?<file>[^:]+):(?<line>\d+):(?:(?<col>\d+):)?";
+        let line = "src/it's.rs:42: oops";
+        assert_eq!(
+            render(regex, "vim +{line}:{col:-1} {file} # {0}", line).as_deref(),
+            Ok(r"vim +42:1 'src/it'\''s.rs' # 'src/it'\''s.rs:42:'")
+        );
+        assert_eq!(
+            render(regex, r#"echo "{!file|basename}" {file|dirname} {2}"#, line).as_deref(),
+            Ok(r#"echo "it's.rs" src 42"#)
+        );
+        assert_eq!(
+            render(regex, "awk '{ print $1 }' {{x}} ${HOME}", line).as_deref(),
+            Ok("awk '{ print $1 }' {x} ${HOME}")
+        );
+
+        let abspath = render(regex, "{!file|abspath}", line).unwrap();
+        assert!(abspath.starts_with('/') && abspath.ends_with("/src/it's.rs"));
+
+        assert!(render(regex, "vim {fiel}", line).is_err());
+        assert!(render(regex, "vim {file|frob}", line).is_err());
+        assert!(render(regex, "vim {file:1}", line).is_err());
+        assert!(render(regex, "vim {file", line).is_err());
+    }
+
+    #[test]
+    fn test_argv() {
+        assert_eq!(
+            render(r"(\S+):(\d+)", r"argv: vim +\2 {1|basename}", "a/b.rs:7").as_deref(),
+            Ok("argv: vim +7 b.rs")
+        );
+    }
+}
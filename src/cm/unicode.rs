@@ -37,7 +37,7 @@ pub fn width_substr(s: &str, range: Range<usize>) -> Option<(&str, (usize, usize
                 _ => break,
             }
         }
-        m as usize
+        m
     };
 
     s.get(start_bytes..end_bytes)
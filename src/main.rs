@@ -3,15 +3,95 @@ mod cm;
 use cm::*;
 use ncurses::*;
 use pcre2::bytes::RegexBuilder;
-use std::env::var;
-use std::fs::{create_dir_all, File};
+use std::env::{current_dir, set_current_dir, var};
+use std::fs::{create_dir_all, read_to_string, File};
+use std::os::unix::io::FromRawFd;
 use std::panic::{set_hook, take_hook};
 use std::path::PathBuf;
+use std::process::exit;
 
 fn main() {
     let locale_conf = LcCategory::all;
     setlocale(locale_conf, "en_US.UTF-8");
 
+    let args = match args::Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("cm: {}", err);
+            exit(2);
+        }
+    };
+
+    if args.help {
+        print!("{}", args::USAGE);
+        exit(0);
+    }
+
+    if args.version {
+        println!("cm {}", env!("CARGO_PKG_VERSION"));
+        exit(0);
+    }
+
+    if let Some(cwd) = &args.cwd {
+        if let Err(err) = set_current_dir(cwd) {
+            eprintln!(
+                "cm: could not change directory to {}: {}",
+                cwd.display(),
+                err
+            );
+            exit(2);
+        }
+    }
+
+    let user_provided_cmdline = args.cmdline();
+
+    let user_config_path = args.config.clone().unwrap_or_else(|| {
+        let xdg_config_dir = var("XDG_CONFIG_HOME").map(PathBuf::from);
+        let home_config_dir = var("HOME").map(PathBuf::from).map(|x| x.join(".config"));
+        xdg_config_dir
+            .or(home_config_dir)
+            .map(|p| p.join(CONFIG_FILE_NAME))
+            .expect("Could not find path to configuration file")
+    });
+
+    let project_config_path = current_dir().ok().and_then(|cwd| project_path(&cwd));
+    let (mut config, mut profile, config_errors) =
+        ConfigLayers::load(system_path(), user_config_path, project_config_path);
+
+    // NOTE: the regex and the command provided through the CLI are not
+    // saved to the configuration
+    profile.regex_override = args.regex.clone();
+    profile.cmd_override = args.cmd.clone();
+
+    // NOTE: the shell provided through the CLI is not saved to the
+    // configuration
+    let shell = shell::Shell {
+        path: args.shell.clone().unwrap_or_else(|| profile.shell.clone()),
+        args: profile.shell_args.clone(),
+    };
+
+    // NOTE: `make 2>&1 | cm` - the output is read from stdin, so the
+    // keyboard has to be read from the terminal directly
+    let input_is_piped = unsafe { libc::isatty(0) } == 0;
+
+    if let Some(format) = args.batch {
+        if user_provided_cmdline.is_none() && args.files.is_empty() && !input_is_piped {
+            eprintln!("cm: --batch requires a command to run or an input piped into cm");
+            exit(2);
+        }
+        for err in config_errors.iter() {
+            eprintln!("cm: {}", err);
+        }
+        exit(batch::run(
+            user_provided_cmdline.as_deref(),
+            &args.files,
+            &shell,
+            &profile,
+            format,
+            args.export.as_deref(),
+        ));
+    }
+
     set_hook(Box::new({
         let default_hook = take_hook();
         move |payload| {
@@ -20,7 +100,21 @@ fn main() {
         }
     }));
 
-    initscr();
+    if input_is_piped {
+        let tty = unsafe {
+            libc::fopen(
+                "/dev/tty\0".as_ptr() as *const libc::c_char,
+                "r+\0".as_ptr() as *const libc::c_char,
+            )
+        };
+        if tty.is_null() {
+            eprintln!("cm: could not open /dev/tty for reading the keyboard");
+            exit(1);
+        }
+        newterm(None, tty, tty);
+    } else {
+        initscr();
+    }
     ctrlc::init();
 
     // NOTE(timeout): timeout(16) is a very important setting of ncurses for our
@@ -39,30 +133,69 @@ fn main() {
     keypad(stdscr(), true);
     init_style();
 
-    let config_path = {
-        const CONFIG_FILE_NAME: &str = "cm.conf";
-        let xdg_config_dir = var("XDG_CONFIG_HOME").map(PathBuf::from);
-        let home_config_dir = var("HOME").map(PathBuf::from).map(|x| x.join(".config"));
-        xdg_config_dir
-            .or(home_config_dir)
-            .map(|p| p.join(CONFIG_FILE_NAME))
-            .expect("Could not find path to configuration file")
-    };
+    let mut key_map_settings = KeyMapSettings::new();
 
-    let mut profile = if config_path.exists() {
-        Profile::from_file(migration::read_and_migrate_file(&config_path), &config_path)
-    } else {
-        Profile::initial()
-    };
+    let mut global = Global::new(user_provided_cmdline.clone(), profile.pty);
+    global.export_path = args.export.clone();
 
-    let mut key_map_settings = KeyMapSettings::new();
+    let mut output_buffer = OutputBuffer::new(shell.clone());
+
+    if !config_errors.is_empty() {
+        output_buffer
+            .status_line
+            .set_error(config_errors.join("; "));
+    }
+
+    if args.resume {
+        match session::path().map(|path| (read_to_string(&path), path)) {
+            Some((Ok(input), _)) => match session::load(&input) {
+                Ok((tabs, current_tab)) => output_buffer.restore(tabs, current_tab),
+                Err(err) => output_buffer.status_line.set_error(err),
+            },
+            Some((Err(err), path)) => output_buffer.status_line.set_error(format!(
+                "Could not read {}: {}",
+                path.display(),
+                err
+            )),
+            None => output_buffer
+                .status_line
+                .set_error("Could not find path to session file".to_string()),
+        }
+    }
+
+    for path in args.files.iter() {
+        match File::open(path) {
+            Ok(file) => {
+                let source = path.display().to_string();
+                output_buffer.fresh_tab();
+                output_buffer.tab_mut().name = Some(source.clone());
+                output_buffer
+                    .tab_mut()
+                    .open_input(&source, file, args.follow);
+            }
+            Err(err) => output_buffer.status_line.set_error(format!(
+                "Could not open {}: {}",
+                path.display(),
+                err
+            )),
+        }
+    }
 
-    let mut global = Global::new(std::env::args().nth(1));
+    if user_provided_cmdline.is_none() && args.files.is_empty() && input_is_piped {
+        output_buffer.fresh_tab();
+        output_buffer
+            .tab_mut()
+            .open_input("stdin", unsafe { File::from_raw_fd(0) }, false);
+    }
 
-    let mut output_buffer = OutputBuffer::new();
+    if let Some(cmdline) = user_provided_cmdline {
+        output_buffer.fresh_tab();
+        output_buffer.tab_mut().user_provided_cmdline = Some(cmdline.clone());
+        output_buffer.run_cmdline(cmdline, global.pty);
+    }
 
-    if global.user_provided_cmdline.is_none() {
-        output_buffer.lists.push(ItemList::new());
+    if output_buffer.tabs.len() == 1 && output_buffer.tab().lists.is_empty() {
+        output_buffer.tab_mut().lists.push(OutputList::new());
         output_buffer.push("Welcome to cm!".to_string());
         output_buffer.push("- Use arrows or vim style hjkl to navigate.".to_string());
         output_buffer.push("- Press F3 to enter a command to run.".to_string());
@@ -73,10 +206,7 @@ fn main() {
         output_buffer.push("    $ cm '<command>'".to_string());
         // TODO(#173): tutorial does not respect current key bindings
     }
-
-    if let Some(cmdline) = global.user_provided_cmdline.clone() {
-        output_buffer.run_cmdline(cmdline, &profile.shell);
-    }
+    output_buffer.sync_cmdline(&mut global);
 
     // NOTE(rerender): because of the asynchronous nature of the application the
     // rendering process could be invoked every 16 millisecond (See NOTE(timeout)),
@@ -110,9 +240,11 @@ fn main() {
                         BottomState::Cmdline => {
                             global.user_provided_cmdline =
                                 Some(global.bottom_edit_field.edit_field.buffer.clone());
+                            output_buffer.tab_mut().user_provided_cmdline =
+                                global.user_provided_cmdline.clone();
                             output_buffer.run_cmdline(
                                 global.bottom_edit_field.edit_field.buffer.clone(),
-                                &profile.shell,
+                                global.pty,
                             );
                         }
                         BottomState::Search => {
@@ -121,10 +253,46 @@ fn main() {
                                 .ucp(true)
                                 .build(global.bottom_edit_field.edit_field.buffer.as_str())
                             {
-                                output_buffer.jump_to_next_match(&regex);
+                                output_buffer.tab_mut().jump_to_next_match(|lines, index| {
+                                    lines[index].is_match(&regex)
+                                });
                                 global.search_regex = Some(regex);
                             }
                         }
+                        BottomState::NewTab => {
+                            let cmdline = global.bottom_edit_field.edit_field.buffer.clone();
+                            output_buffer.new_tab(Some(cmdline.clone()));
+                            output_buffer.sync_cmdline(&mut global);
+                            output_buffer.run_cmdline(cmdline, global.pty);
+                        }
+                        BottomState::TabName => {
+                            let name = global.bottom_edit_field.edit_field.buffer.clone();
+                            output_buffer.tab_mut().name =
+                                if name.is_empty() { None } else { Some(name) };
+                        }
+                        BottomState::RuleName => {
+                            let name = global.bottom_edit_field.edit_field.buffer.clone();
+                            if let Err(err) = profile.add_rule(name) {
+                                output_buffer.status_line.set_error(err);
+                            }
+                        }
+                        BottomState::ExportPath => {
+                            let path = PathBuf::from(&global.bottom_edit_field.edit_field.buffer);
+                            let exported = match profile.matcher() {
+                                Some(Ok(matcher)) => output_buffer.export(&path, &matcher),
+                                Some(Err(err)) => Err(err.to_string()),
+                                None => Err("No regex is selected".to_string()),
+                            };
+                            match exported {
+                                Ok(count) => output_buffer.status_line.set_text(format!(
+                                    "Exported {} locations to {}",
+                                    count,
+                                    path.display()
+                                )),
+                                Err(err) => output_buffer.status_line.set_error(err),
+                            }
+                            global.export_path = Some(path);
+                        }
                         BottomState::Nothing => {
                             unreachable!("Unexpected bottom state");
                         }
@@ -140,21 +308,31 @@ fn main() {
                 }
             } else {
                 match global.mode {
-                    Mode::Output => {
-                        output_buffer.handle_key(key_stroke, &profile, &mut global, &profile.shell)
-                    }
+                    Mode::Output => output_buffer.handle_key(key_stroke, &mut profile, &mut global),
                     Mode::Regexs => {
+                        profile.regex_override = None;
                         profile
                             .regex_list
                             .handle_key(key_stroke, &profile.key_map, &mut global);
                         output_buffer.refresh_status_line(&profile);
                     }
                     Mode::Cmds => {
+                        profile.cmd_override = None;
                         profile
                             .cmd_list
                             .handle_key(key_stroke, &profile.key_map, &mut global);
                         output_buffer.refresh_status_line(&profile);
                     }
+                    Mode::History => {
+                        output_buffer.handle_history_key(key_stroke, &profile, &mut global)
+                    }
+                    Mode::Quickfix => {
+                        output_buffer.handle_quickfix_key(key_stroke, &profile, &mut global)
+                    }
+                    Mode::Rules => {
+                        profile.handle_rules_key(key_stroke, &mut global);
+                        output_buffer.refresh_status_line(&profile);
+                    }
                 }
             }
         }
@@ -171,6 +349,29 @@ fn main() {
             // to be reflected by rerendering the screen.
             rerender = rerender || output_buffer_changed;
         }
+
+        // NOTE: another cm or the user may have changed the configuration
+        // file in the meantime
+        if let Some(reloaded) = config.poll(&mut profile) {
+            match reloaded {
+                Ok(text) => output_buffer.status_line.set_text(text),
+                Err(err) => output_buffer.status_line.set_error(err),
+            }
+            rerender = true;
+        }
+
+        // NOTE: the regex is not picked for the user while they are
+        // picking it themselves. The pick is checked on every iteration
+        // rather than on rerender, so the last of the output is scored
+        // too once the output stops coming.
+        if global.mode == Mode::Output {
+            if let Some(list) = output_buffer.tab().current_list() {
+                if profile.select_best_regex(list) {
+                    output_buffer.refresh_status_line(&profile);
+                    rerender = true;
+                }
+            }
+        }
         // END ASYNC CHILD OUTPUT SECTION //////////////////////////////
 
         // BEGIN RENDER SECTION //////////////////////////////
@@ -189,10 +390,6 @@ fn main() {
             if global.key_map_settings {
                 key_map_settings.render(Rect { x: 0, y: 0, w, h }, true);
             } else {
-                if h >= 1 {
-                    output_buffer.status_line.render(h - 1);
-                }
-
                 let working_rect = Rect {
                     x: 0,
                     y: 0,
@@ -201,23 +398,42 @@ fn main() {
                 };
 
                 match global.mode {
-                    Mode::Output => {
-                        output_buffer.render(working_rect, true, profile.current_regex())
-                    }
+                    Mode::Output => output_buffer.render(working_rect, true, profile.matcher()),
                     Mode::Regexs => {
                         let (output_buffer_rect, profile_rect) = working_rect.horizontal_split(3);
-                        output_buffer.render(output_buffer_rect, false, profile.current_regex());
+                        output_buffer.render(output_buffer_rect, false, profile.matcher());
                         profile
                             .regex_list
                             .render(profile_rect, true, &mut global.cursor);
                     }
                     Mode::Cmds => {
                         let (output_buffer_rect, profile_rect) = working_rect.horizontal_split(3);
-                        output_buffer.render(output_buffer_rect, false, profile.current_regex());
+                        output_buffer.render(output_buffer_rect, false, profile.matcher());
                         profile
                             .cmd_list
                             .render(profile_rect, true, &mut global.cursor);
                     }
+                    Mode::History => {
+                        let (output_buffer_rect, history_rect) = working_rect.horizontal_split(3);
+                        output_buffer.render(output_buffer_rect, false, profile.matcher());
+                        output_buffer.render_history(history_rect, true);
+                    }
+                    Mode::Quickfix => {
+                        let (output_buffer_rect, quickfix_rect) = working_rect.horizontal_split(3);
+                        output_buffer.render(output_buffer_rect, false, profile.matcher());
+                        output_buffer.render_quickfix(quickfix_rect, true, profile.matcher());
+                    }
+                    Mode::Rules => {
+                        let (output_buffer_rect, rules_rect) = working_rect.horizontal_split(3);
+                        output_buffer.render(output_buffer_rect, false, profile.matcher());
+                        profile.render_rules(rules_rect, true);
+                    }
+                }
+
+                // NOTE: rendered after the output, since rendering the
+                // output updates the severity counts of the status line
+                if h >= 1 {
+                    output_buffer.status_line.render(h - 1, w);
                 }
 
                 if global.bottom_state != BottomState::Nothing {
@@ -237,12 +453,42 @@ fn main() {
         rerender = false;
     }
 
-    output_buffer.kill_the_child();
+    output_buffer.kill_the_children();
 
-    config_path.parent().map(create_dir_all);
-    profile
-        .to_file(&mut File::create(config_path).expect("Could not open configuration file"))
-        .expect("Could not save configuration");
+    // NOTE: exported after the children are killed, so the output does
+    // not change under our feet
+    let exported = match (&args.export, profile.matcher()) {
+        (Some(path), Some(Ok(matcher))) => Some(output_buffer.export(path, &matcher)),
+        _ => None,
+    };
+
+    let mut errors = Vec::new();
+
+    if profile.session {
+        if let Some(session_path) = session::path() {
+            session_path.parent().map(create_dir_all);
+            if let Err(err) = File::create(&session_path)
+                .and_then(|mut file| session::save(&output_buffer, &mut file))
+            {
+                errors.push(format!(
+                    "Could not save session to {}: {}",
+                    session_path.display(),
+                    err
+                ));
+            }
+        }
+    }
+
+    errors.extend(config.save(&mut profile));
 
     endwin();
+
+    for err in errors.iter() {
+        eprintln!("cm: {}", err);
+    }
+
+    if let Some(Err(err)) = exported {
+        eprintln!("cm: {}", err);
+        exit(2);
+    }
 }